@@ -16,29 +16,27 @@ fn main() {
     println!("It's {} relative humidity", rh);
     let pressure = sense_hat.get_pressure().expect("Couldn't get pressure");
     println!("The pressure is {}", pressure);
+    // Keep the fusion fed with every sample, but only print a few times
+    // a second
+    let poll_interval = sense_hat.recommended_poll_interval();
+    let mut last_print = ::std::time::Instant::now();
     loop {
         let orientation = sense_hat
-            .get_orientation()
+            .get_orientation_timeout(::std::time::Duration::from_secs(1))
             .expect("Couldn't get orientation");
-        println!(
-            "Fusion orientation: {}, {}, {}",
-            orientation.roll, orientation.pitch, orientation.yaw
-        );
-        if let Ok(heading) = sense_hat.get_compass() {
-            println!("Compass heading  :  {}", heading);
+        if last_print.elapsed() >= ::std::time::Duration::from_millis(250) {
+            last_print = ::std::time::Instant::now();
+            println!("Fusion orientation: {}", orientation);
+            if let Ok(heading) = sense_hat.get_compass() {
+                println!("Compass heading  :  {}", heading);
+            }
+            if let Ok(orientation) = sense_hat.get_gyro() {
+                println!("Gyro orientation :  {}", orientation);
+            }
+            if let Ok(orientation) = sense_hat.get_accel() {
+                println!("Accel orientation:  {}", orientation);
+            }
         }
-        if let Ok(orientation) = sense_hat.get_gyro() {
-            println!(
-                "Gyro orientation :  {}, {}, {}",
-                orientation.roll, orientation.pitch, orientation.yaw
-            );
-        }
-        if let Ok(orientation) = sense_hat.get_accel() {
-            println!(
-                "Accel orientation:  {}, {}, {}",
-                orientation.roll, orientation.pitch, orientation.yaw
-            );
-        }
-        ::std::thread::sleep(::std::time::Duration::from_millis(250));
+        ::std::thread::sleep(poll_interval);
     }
 }
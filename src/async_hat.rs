@@ -0,0 +1,482 @@
+//! * Using the Sense HAT from async code
+//!
+//! Nothing here depends on a particular runtime: the futures are plain
+//! `std::future::Future`s, woken from a helper thread, so they work under
+//! tokio, async-std or anything else. `JoystickStream` isn't a
+//! `futures::Stream`, but has the same `poll_next`, so it can be turned
+//! into one with
+//! `futures::stream::poll_fn(move |cx| Pin::new(&mut stream).poll_next(cx))`.
+//!
+//! The I2C devices can't be shared between threads, so `AsyncSenseHat` has
+//! a worker thread which owns a `SenseHat` and does the blocking reads for
+//! it, one at a time, as `spawn_sampler` does.
+
+use super::{
+    joystick_read_error, Environment, Joystick, JoystickEvent, SenseHat, SenseHatError,
+    SenseHatResult,
+};
+use i2cdev::core::I2CDevice;
+use i2cdev::linux::LinuxI2CDevice;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::future::Future;
+use std::io::{self, Write};
+use std::os::raw::{c_int, c_short, c_ulong};
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::pin::Pin;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+type Job<D> = Box<dyn FnOnce(&mut SenseHat<'static, D>) + Send>;
+
+/// A `SenseHat` for async code. Create one with `AsyncSenseHat::new()` or
+/// `SenseHatBuilder::build_async`.
+///
+/// ```no_run,edition2018
+/// # async fn example() -> sensehat::SenseHatResult<()> {
+/// let hat = sensehat::AsyncSenseHat::new()?;
+/// let environment = hat.read_environment().await?;
+/// let mut joystick = hat.joystick_stream().await?;
+/// while let Some(event) = joystick.next_event().await {
+///     println!("{:?} {:?}", event?, environment.temperature());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct AsyncSenseHat<D = LinuxI2CDevice>
+where
+    D: I2CDevice + 'static,
+{
+    jobs: Mutex<mpsc::Sender<Job<D>>>,
+}
+
+impl AsyncSenseHat {
+    /// Try and create a new AsyncSenseHat object, with the same settings as
+    /// `SenseHat::new()`.
+    pub fn new() -> SenseHatResult<AsyncSenseHat> {
+        AsyncSenseHat::start(SenseHat::new)
+    }
+}
+
+impl<D> AsyncSenseHat<D>
+where
+    D: I2CDevice + 'static,
+    SenseHatError: From<D::Error>,
+{
+    /// Starts the worker thread, which creates its `SenseHat` with `build`.
+    /// If that fails, so does this.
+    pub(crate) fn start<F>(build: F) -> SenseHatResult<AsyncSenseHat<D>>
+    where
+        F: FnOnce() -> SenseHatResult<SenseHat<'static, D>> + Send + 'static,
+    {
+        let (jobs, rx) = mpsc::channel::<Job<D>>();
+        let (started_tx, started) = mpsc::channel();
+        ::std::thread::spawn(move || {
+            let mut hat = match build() {
+                Ok(hat) => {
+                    let _ = started_tx.send(Ok(()));
+                    hat
+                }
+                Err(err) => {
+                    let _ = started_tx.send(Err(err));
+                    return;
+                }
+            };
+            // This stops once the AsyncSenseHat, and with it the sender, is
+            // dropped.
+            for job in rx {
+                job(&mut hat);
+            }
+        });
+        started.recv().unwrap_or(Err(SenseHatError::GenericError))?;
+        Ok(AsyncSenseHat {
+            jobs: Mutex::new(jobs),
+        })
+    }
+
+    /// Reads every environmental sensor, as `SenseHat::get_environment`
+    /// does, without blocking the caller.
+    pub fn read_environment(&self) -> impl Future<Output = SenseHatResult<Environment>> {
+        self.run(|hat| hat.get_environment())
+    }
+
+    /// Opens the joystick, as `SenseHat::joystick` does, and returns a
+    /// stream of its events.
+    pub fn joystick_stream(&self) -> impl Future<Output = SenseHatResult<JoystickStream>> {
+        self.run(|hat| hat.joystick_stream())
+    }
+
+    /// Runs `f` on the worker thread, and returns a future of its result.
+    fn run<T, F>(&self, f: F) -> Reply<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut SenseHat<'static, D>) -> SenseHatResult<T> + Send + 'static,
+    {
+        let (reply, completer) = Reply::new();
+        let job: Job<D> = Box::new(move |hat| completer.complete(f(hat)));
+        // If the worker has gone, the job (and its completer) is dropped,
+        // which finishes the reply with an error.
+        if let Ok(jobs) = self.jobs.lock() {
+            let _ = jobs.send(job);
+        }
+        reply
+    }
+}
+
+/// The result of a job on the worker thread, once it has run.
+struct Reply<T> {
+    slot: Arc<Mutex<Slot<T>>>,
+}
+
+struct Slot<T> {
+    value: Option<SenseHatResult<T>>,
+    waker: Option<Waker>,
+}
+
+/// Finishes a `Reply`. If it is dropped without being used, because the
+/// worker thread has stopped, the reply is a `GenericError`.
+struct Completer<T> {
+    slot: Option<Arc<Mutex<Slot<T>>>>,
+}
+
+impl<T> Reply<T> {
+    fn new() -> (Reply<T>, Completer<T>) {
+        let slot = Arc::new(Mutex::new(Slot {
+            value: None,
+            waker: None,
+        }));
+        let completer = Completer {
+            slot: Some(slot.clone()),
+        };
+        (Reply { slot }, completer)
+    }
+}
+
+impl<T> Future for Reply<T> {
+    type Output = SenseHatResult<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<SenseHatResult<T>> {
+        let mut slot = match self.slot.lock() {
+            Ok(slot) => slot,
+            Err(_) => return Poll::Ready(Err(SenseHatError::GenericError)),
+        };
+        match slot.value.take() {
+            Some(value) => Poll::Ready(value),
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T> Completer<T> {
+    fn complete(mut self, value: SenseHatResult<T>) {
+        self.finish(value);
+    }
+
+    fn finish(&mut self, value: SenseHatResult<T>) {
+        if let Some(slot) = self.slot.take() {
+            if let Ok(mut slot) = slot.lock() {
+                slot.value = Some(value);
+                if let Some(waker) = slot.waker.take() {
+                    waker.wake();
+                }
+            }
+        }
+    }
+}
+
+impl<T> Drop for Completer<T> {
+    fn drop(&mut self) {
+        self.finish(Err(SenseHatError::GenericError));
+    }
+}
+
+/// The joystick's events, from `SenseHat::joystick_stream` or
+/// `AsyncSenseHat::joystick_stream`, one at a time from `next_event`.
+///
+/// This isn't a `futures::Stream`, as this crate doesn't depend on
+/// `futures`, but `poll_next` has the same signature, so
+/// `futures::stream::poll_fn` can make one from it.
+///
+/// A thread waits for the joystick with `poll(2)`, so events are delivered
+/// as soon as they happen. It stops when this is dropped. If the joystick
+/// can't be read, for example because it has been disconnected, this gives
+/// the error (`JoystickDisconnected` if it has gone) and then ends.
+pub struct JoystickStream {
+    shared: Arc<Mutex<StreamState>>,
+    /// Writing to this wakes the thread up so that it stops.
+    stop: File,
+}
+
+struct StreamState {
+    events: VecDeque<SenseHatResult<JoystickEvent>>,
+    waker: Option<Waker>,
+    /// The joystick thread has stopped.
+    finished: bool,
+}
+
+impl JoystickStream {
+    /// Starts a thread which reads events from `joystick`.
+    pub(crate) fn new(joystick: Joystick) -> SenseHatResult<JoystickStream> {
+        let (stopped, stop) = pipe().map_err(SenseHatError::JoystickError)?;
+        let shared = Arc::new(Mutex::new(StreamState {
+            events: VecDeque::new(),
+            waker: None,
+            finished: false,
+        }));
+        let thread_shared = shared.clone();
+        ::std::thread::spawn(move || read_events(joystick, &stopped, &thread_shared));
+        Ok(JoystickStream { shared, stop })
+    }
+
+    /// Returns the next event if there is one, or arranges for the task to
+    /// be woken when there is. Returns `Poll::Ready(None)` once the
+    /// joystick can't be read any more.
+    pub fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<SenseHatResult<JoystickEvent>>> {
+        let mut state = match self.shared.lock() {
+            Ok(state) => state,
+            Err(_) => return Poll::Ready(None),
+        };
+        if let Some(event) = state.events.pop_front() {
+            Poll::Ready(Some(event))
+        } else if state.finished {
+            Poll::Ready(None)
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+
+    /// Waits for the next event. This gives `None` once the joystick can't
+    /// be read any more.
+    pub fn next_event(
+        &mut self,
+    ) -> impl Future<Output = Option<SenseHatResult<JoystickEvent>>> + '_ {
+        ::std::future::poll_fn(move |cx| Pin::new(&mut *self).poll_next(cx))
+    }
+}
+
+impl Drop for JoystickStream {
+    fn drop(&mut self) {
+        let _ = self.stop.write_all(&[0]);
+    }
+}
+
+/// The body of the joystick thread: reads every event that's waiting, then
+/// sleeps in `poll(2)` until there are more, or `stopped` is readable.
+fn read_events(mut joystick: Joystick, stopped: &File, shared: &Mutex<StreamState>) {
+    loop {
+        let mut events = Vec::new();
+        let error = loop {
+            match joystick.try_read_event() {
+                Ok(Some(event)) => events.push(Ok(event)),
+                Ok(None) => break None,
+                Err(err) => break Some(joystick_read_error(err)),
+            }
+        };
+        let error = error.or_else(|| {
+            if events.is_empty() {
+                wait(&joystick, stopped)
+                    .err()
+                    .map(SenseHatError::JoystickError)
+            } else {
+                None
+            }
+        });
+        let mut state = match shared.lock() {
+            Ok(state) => state,
+            Err(_) => return,
+        };
+        let finished = error.is_some();
+        state.events.extend(events);
+        state.events.extend(error.map(Err));
+        state.finished = finished;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        if finished || is_readable(stopped) {
+            return;
+        }
+    }
+}
+
+#[repr(C)]
+struct PollFd {
+    fd: c_int,
+    events: c_short,
+    revents: c_short,
+}
+
+/// From `poll.h`.
+const POLLIN: c_short = 1;
+/// From `fcntl.h`.
+const O_CLOEXEC: c_int = 0o2_000_000;
+
+extern "C" {
+    fn poll(fds: *mut PollFd, nfds: c_ulong, timeout: c_int) -> c_int;
+    fn pipe2(fds: *mut c_int, flags: c_int) -> c_int;
+}
+
+/// Waits until `joystick` or `stopped` has something to read.
+fn wait(joystick: &Joystick, stopped: &File) -> io::Result<()> {
+    let mut fds = [
+        PollFd {
+            fd: joystick.as_raw_fd(),
+            events: POLLIN,
+            revents: 0,
+        },
+        PollFd {
+            fd: stopped.as_raw_fd(),
+            events: POLLIN,
+            revents: 0,
+        },
+    ];
+    loop {
+        if unsafe { poll(fds.as_mut_ptr(), fds.len() as c_ulong, -1) } >= 0 {
+            return Ok(());
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
+
+/// Does `file` have something to read, right now?
+fn is_readable(file: &File) -> bool {
+    let mut fds = [PollFd {
+        fd: file.as_raw_fd(),
+        events: POLLIN,
+        revents: 0,
+    }];
+    unsafe { poll(fds.as_mut_ptr(), 1, 0) > 0 }
+}
+
+/// Opens a pipe, returning its read and write ends.
+fn pipe() -> io::Result<(File, File)> {
+    let mut fds = [0 as c_int; 2];
+    if unsafe { pipe2(fds.as_mut_ptr(), O_CLOEXEC) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    unsafe { Ok((File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1]))) }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mock::MockI2CDevice;
+    use std::task::Wake;
+    use std::thread::Thread;
+
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// Runs `future` to completion on this thread.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = Box::pin(future);
+        let waker = Waker::from(Arc::new(ThreadWaker(::std::thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            ::std::thread::park();
+        }
+    }
+
+    // With the `rtimu` feature the IMU needs real hardware, so the mock
+    // tests only run without it.
+    #[cfg(not(feature = "rtimu"))]
+    #[test]
+    fn check_read_environment() {
+        let hat = AsyncSenseHat::start(|| {
+            SenseHat::with_devices(MockI2CDevice::hts221(), MockI2CDevice::lps25h())
+        })
+        .unwrap();
+        let environment = block_on(hat.read_environment()).unwrap();
+        assert!(environment.pressure.is_some());
+        assert!(environment.humidity.is_some());
+    }
+
+    #[cfg(not(feature = "rtimu"))]
+    #[test]
+    fn check_start_failure() {
+        let result = AsyncSenseHat::start(|| {
+            SenseHat::with_devices(MockI2CDevice::new(), MockI2CDevice::lps25h())
+        });
+        match result {
+            Err(SenseHatError::WrongChip("HTS221", 0)) => {}
+            Err(err) => panic!("expected WrongChip, got {:?}", err),
+            Ok(_) => panic!("expected WrongChip"),
+        }
+    }
+
+    #[test]
+    fn check_reply_dropped() {
+        let (reply, completer) = Reply::<()>::new();
+        ::std::thread::spawn(move || drop(completer));
+        match block_on(reply) {
+            Err(SenseHatError::GenericError) => {}
+            other => panic!("expected GenericError, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn check_joystick_stream_error() {
+        // Reading a directory fails straight away, which ends the stream
+        let joystick = Joystick::open("/").unwrap();
+        let mut stream = JoystickStream::new(joystick).unwrap();
+        match block_on(stream.next_event()) {
+            Some(Err(SenseHatError::JoystickError(_))) => {}
+            other => panic!("expected JoystickError, got {:?}", other),
+        }
+        assert!(block_on(stream.next_event()).is_none());
+    }
+
+    #[test]
+    fn check_joystick_stream_wakes() {
+        // A pipe stands in for the input device
+        let (rx, mut tx) = pipe().unwrap();
+        let joystick = Joystick::open(format!("/proc/self/fd/{}", rx.as_raw_fd())).unwrap();
+        drop(rx);
+        let mut stream = JoystickStream::new(joystick).unwrap();
+        let long = ::std::mem::size_of::<isize>();
+        // One second in, KEY_UP (103) pressed
+        let mut event = vec![0u8; 2 * long + 8];
+        event[0] = 1;
+        event[2 * long] = 1;
+        event[2 * long + 2] = 103;
+        event[2 * long + 4] = 1;
+        let writer = ::std::thread::spawn(move || {
+            ::std::thread::sleep(::std::time::Duration::from_millis(50));
+            tx.write_all(&event).unwrap();
+            tx
+        });
+        let event = block_on(stream.next_event()).unwrap().unwrap();
+        assert_eq!(event.direction, ::Direction::Up);
+        assert_eq!(event.action, ::Action::Pressed);
+        assert_eq!(event.timestamp, ::std::time::Duration::from_secs(1));
+        // Once the stream is dropped, its thread closes the joystick, which
+        // was the last reader of the pipe
+        let mut tx = writer.join().unwrap();
+        drop(stream);
+        let closed = (0..100).any(|_| {
+            ::std::thread::sleep(::std::time::Duration::from_millis(10));
+            tx.write_all(&[0]).is_err()
+        });
+        assert!(closed);
+    }
+}
+
+// End of file
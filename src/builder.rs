@@ -0,0 +1,229 @@
+//! * A builder for `SenseHat`, for when the defaults aren't right
+
+#[cfg(feature = "async")]
+use super::AsyncSenseHat;
+use super::{
+    hts221, i2c_timeout, lps25h, Reading, RetryPolicy, Rotation, SenseHat, SenseHatResult,
+    Temperature, Ticker,
+};
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// Builds a `SenseHat` with non-default settings.
+///
+/// ```no_run
+/// use sensehat::SenseHatBuilder;
+/// let hat = SenseHatBuilder::new()
+///     .i2c_bus("/dev/i2c-0")
+///     .imu_settings("/home/pi/.config/sense_hat/RTIMULib.ini")
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct SenseHatBuilder {
+    i2c_bus: String,
+    hts221_addr: u16,
+    lps25h_addr: u16,
+    imu_settings: Option<PathBuf>,
+    i2c_timeout: Duration,
+    temperature_offset: Option<Temperature>,
+    retry: Option<RetryPolicy>,
+    prime: usize,
+    rotation: Rotation,
+    #[cfg(feature = "led-matrix")]
+    framebuffer: Option<String>,
+    #[cfg(feature = "led-matrix")]
+    clear_on_drop: bool,
+    joystick: Option<String>,
+}
+
+impl SenseHatBuilder {
+    /// Starts with the same settings as `SenseHat::new()`.
+    pub fn new() -> SenseHatBuilder {
+        SenseHatBuilder {
+            i2c_bus: String::from("/dev/i2c-1"),
+            hts221_addr: hts221::I2C_ADDRESS,
+            lps25h_addr: lps25h::I2C_ADDRESS,
+            imu_settings: None,
+            i2c_timeout: i2c_timeout::DEFAULT_TIMEOUT,
+            temperature_offset: None,
+            retry: None,
+            prime: 0,
+            rotation: Rotation::Deg0,
+            #[cfg(feature = "led-matrix")]
+            framebuffer: None,
+            #[cfg(feature = "led-matrix")]
+            clear_on_drop: false,
+            joystick: None,
+        }
+    }
+
+    /// Uses the sensors on the given I2C bus, instead of `/dev/i2c-1`.
+    pub fn i2c_bus(mut self, path: &str) -> SenseHatBuilder {
+        self.i2c_bus = String::from(path);
+        self
+    }
+
+    /// Uses the given address for the HTS221 humidity sensor, instead of
+    /// 0x5f.
+    pub fn hts221_addr(mut self, addr: u16) -> SenseHatBuilder {
+        self.hts221_addr = addr;
+        self
+    }
+
+    /// Uses the given address for the LPS25H pressure sensor, instead of
+    /// 0x5c.
+    pub fn lps25h_addr(mut self, addr: u16) -> SenseHatBuilder {
+        self.lps25h_addr = addr;
+        self
+    }
+
+    /// Loads the IMU settings from the given `RTIMULib` settings file. See
+    /// `SenseHat::with_imu_settings`.
+    pub fn imu_settings<P>(mut self, settings: P) -> SenseHatBuilder
+    where
+        P: Into<PathBuf>,
+    {
+        self.imu_settings = Some(settings.into());
+        self
+    }
+
+    /// Sets how long a transfer on the I2C bus can wait for a stuck bus
+    /// before failing with an `I2CError`, instead of 1 second. The kernel
+    /// counts in 10 ms steps, so this is rounded up to a multiple of 10 ms.
+    /// It applies to everything on the bus, including the IMU.
+    pub fn i2c_timeout(mut self, timeout: Duration) -> SenseHatBuilder {
+        self.i2c_timeout = timeout;
+        self
+    }
+
+    /// Sets the temperature offset. See `SenseHat::set_temperature_offset`.
+    pub fn temperature_offset(mut self, offset: Temperature) -> SenseHatBuilder {
+        self.temperature_offset = Some(offset);
+        self
+    }
+
+    /// Sets how sensor reads are retried after transient I2C errors. See
+    /// `SenseHat::set_retry_policy`.
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> SenseHatBuilder {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// Throws away the first `samples` readings from the humidity and
+    /// pressure sensors once they have been set up, so the first readings
+    /// you get are good. See `SenseHat::prime`. The default is not to.
+    pub fn prime(mut self, samples: usize) -> SenseHatBuilder {
+        self.prime = samples;
+        self
+    }
+
+    /// Turns the LED matrix picture clockwise by `rotation`. See
+    /// `SenseHat::set_rotation`.
+    pub fn rotation(mut self, rotation: Rotation) -> SenseHatBuilder {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Uses the given framebuffer device for the LED matrix, instead of
+    /// detecting it.
+    #[cfg(feature = "led-matrix")]
+    pub fn framebuffer(mut self, path: &str) -> SenseHatBuilder {
+        self.framebuffer = Some(String::from(path));
+        self
+    }
+
+    /// Blanks the LED matrix when the `SenseHat` is dropped. See
+    /// `SenseHat::set_clear_on_drop`.
+    #[cfg(feature = "led-matrix")]
+    pub fn clear_on_drop(mut self, clear: bool) -> SenseHatBuilder {
+        self.clear_on_drop = clear;
+        self
+    }
+
+    /// Uses the given input device for the joystick, instead of detecting
+    /// it.
+    pub fn joystick_path(mut self, path: &str) -> SenseHatBuilder {
+        self.joystick = Some(String::from(path));
+        self
+    }
+
+    /// Try and create the SenseHat object.
+    pub fn build<'a>(&self) -> SenseHatResult<SenseHat<'a>> {
+        let mut hat = SenseHat::open(
+            &self.i2c_bus,
+            self.hts221_addr,
+            self.lps25h_addr,
+            self.imu_settings.as_deref(),
+            self.i2c_timeout,
+        )?;
+        if let Some(offset) = self.temperature_offset {
+            hat.set_temperature_offset(offset);
+        }
+        if let Some(policy) = self.retry {
+            hat.set_retry_policy(policy);
+        }
+        hat.set_rotation(self.rotation);
+        #[cfg(feature = "led-matrix")]
+        {
+            if let Some(ref path) = self.framebuffer {
+                hat.set_framebuffer_path(path);
+            }
+            hat.set_clear_on_drop(self.clear_on_drop);
+        }
+        if let Some(ref path) = self.joystick {
+            hat.set_joystick_path(path);
+        }
+        hat.prime(self.prime)?;
+        Ok(hat)
+    }
+
+    /// Try and create an `AsyncSenseHat` with these settings. Its worker
+    /// thread builds the `SenseHat`, and this returns any error from that.
+    #[cfg(feature = "async")]
+    pub fn build_async(&self) -> SenseHatResult<AsyncSenseHat> {
+        let builder = self.clone();
+        AsyncSenseHat::start(move || builder.build())
+    }
+
+    /// Starts a thread which samples every sensor once per `interval`, and
+    /// returns a channel of the readings.
+    ///
+    /// The I2C devices can't be shared between threads, so the thread builds
+    /// and owns its own `SenseHat` with these settings; don't use another
+    /// `SenseHat` at the same time. If it can't be built, or a reading
+    /// fails, the error is sent and the thread stops. The thread also stops
+    /// when the `Receiver` is dropped.
+    pub fn spawn_sampler(&self, interval: Duration) -> mpsc::Receiver<SenseHatResult<Reading>> {
+        let (tx, rx) = mpsc::channel();
+        let builder = self.clone();
+        ::std::thread::spawn(move || {
+            let mut hat = match builder.build() {
+                Ok(hat) => hat,
+                Err(err) => {
+                    let _ = tx.send(Err(err));
+                    return;
+                }
+            };
+            let mut ticker = Ticker::new(interval);
+            loop {
+                ticker.wait();
+                let reading = hat.get_reading();
+                let failed = reading.is_err();
+                if tx.send(reading).is_err() || failed {
+                    return;
+                }
+            }
+        });
+        rx
+    }
+}
+
+impl Default for SenseHatBuilder {
+    fn default() -> SenseHatBuilder {
+        SenseHatBuilder::new()
+    }
+}
+
+// End of file
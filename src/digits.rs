@@ -0,0 +1,77 @@
+//! * A 3x5 pixel font for showing numbers on the LED matrix
+//!
+//! Unlike the scrolling font, two of these digits fit on the matrix at once.
+
+use super::{put_pixel, Colour};
+
+/// Each digit is five rows of three pixels, with the leftmost pixel in bit 2.
+const DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b001, 0b001, 0b001],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+/// The row of the top of each digit, which centres them vertically.
+const TOP: i32 = 1;
+
+/// Renders a number from 0 to 99 into a raw RGB565 frame: one digit in the
+/// middle, or two side by side with a one pixel gap.
+pub(crate) fn render_number(bytes: &mut [u8; 128], value: u8, fg: Colour, bg: Colour) {
+    for y in 0..8 {
+        for x in 0..8 {
+            put_pixel(bytes, x, y, bg);
+        }
+    }
+    if value < 10 {
+        render_digit(bytes, 2, value, fg);
+    } else {
+        render_digit(bytes, 0, value / 10, fg);
+        render_digit(bytes, 4, value % 10, fg);
+    }
+}
+
+fn render_digit(bytes: &mut [u8; 128], left: i32, digit: u8, colour: Colour) {
+    for (row, bits) in DIGITS[usize::from(digit)].iter().enumerate() {
+        for col in 0..3 {
+            if bits & (0b100 >> col) != 0 {
+                put_pixel(bytes, left + col, TOP + row as i32, colour);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn lit(bytes: &[u8; 128], x: usize, y: usize) -> bool {
+        let offset = (y * 8 + x) * 2;
+        bytes[offset] != 0 || bytes[offset + 1] != 0
+    }
+
+    #[test]
+    fn check_render_number() {
+        let mut bytes = [0u8; 128];
+        render_number(&mut bytes, 17, Colour::WHITE, Colour::BLACK);
+        // The 1's stem and the 7's top right corner
+        assert!(lit(&bytes, 1, 1));
+        assert!(lit(&bytes, 6, 1));
+        assert!(!lit(&bytes, 0, 1));
+        assert!(!lit(&bytes, 3, 1));
+        assert!(!lit(&bytes, 7, 1));
+        // Nothing on the rows above and below the digits
+        assert!((0..8).all(|x| !lit(&bytes, x, 0) && !lit(&bytes, x, 6)));
+        render_number(&mut bytes, 7, Colour::WHITE, Colour::BLACK);
+        assert!(lit(&bytes, 4, 5));
+        assert!(!lit(&bytes, 6, 1));
+    }
+}
+
+// End of file
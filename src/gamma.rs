@@ -0,0 +1,84 @@
+//! * The LED matrix's gamma table
+//!
+//! The Sense HAT framebuffer driver maps each 5-bit colour component to an
+//! LED brightness through a 32-entry gamma table. It has a default table and
+//! a dimmer low-light one, which are chosen and read back with ioctls on the
+//! framebuffer device, as the Python `sense-hat` library does.
+
+use std::fs::File;
+use std::io;
+use std::os::raw::{c_int, c_ulong};
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+/// From the kernel's `rpisense-fb` driver.
+const SENSE_HAT_FB_FBIOGET_GAMMA: c_ulong = 0xf100;
+const SENSE_HAT_FB_FBIORESET_GAMMA: c_ulong = 0xf102;
+const SENSE_HAT_FB_GAMMA_DEFAULT: c_int = 0;
+const SENSE_HAT_FB_GAMMA_LOW: c_int = 1;
+
+/// The driver's low-light table.
+const LOW_LIGHT_GAMMA: [u8; 32] = [
+    0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 3, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 10,
+    10,
+];
+
+extern "C" {
+    fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+}
+
+/// Reads the gamma table from the framebuffer at `path`.
+pub(crate) fn read_gamma(path: &Path) -> io::Result<[u8; 32]> {
+    let file = File::open(path)?;
+    let mut table = [0u8; 32];
+    let result = unsafe {
+        ioctl(
+            file.as_raw_fd(),
+            SENSE_HAT_FB_FBIOGET_GAMMA,
+            table.as_mut_ptr(),
+        )
+    };
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(table)
+}
+
+/// Switches the framebuffer at `path` to the low-light gamma table, or back
+/// to the default one.
+pub(crate) fn set_low_light(path: &Path, low: bool) -> io::Result<()> {
+    let file = File::open(path)?;
+    let table = if low {
+        SENSE_HAT_FB_GAMMA_LOW
+    } else {
+        SENSE_HAT_FB_GAMMA_DEFAULT
+    };
+    if unsafe { ioctl(file.as_raw_fd(), SENSE_HAT_FB_FBIORESET_GAMMA, table) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Is `table` the low-light table? A table set some other way, even a dim
+/// one, isn't.
+pub(crate) fn is_low_light(table: &[u8; 32]) -> bool {
+    *table == LOW_LIGHT_GAMMA
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn check_is_low_light() {
+        assert!(is_low_light(&LOW_LIGHT_GAMMA));
+        let default = [
+            0, 0, 0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 14, 15, 17, 18, 20,
+            21, 23, 25, 27, 29, 31,
+        ];
+        assert!(!is_low_light(&default));
+        assert!(read_gamma(Path::new("/dev/does-not-exist")).is_err());
+    }
+}
+
+// End of file
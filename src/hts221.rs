@@ -4,8 +4,15 @@
 use byteorder::{ByteOrder, LittleEndian};
 use i2cdev::core::I2CDevice;
 
+/// The I2C address of the chip on the Sense HAT.
+pub const I2C_ADDRESS: u16 = 0x5f;
+/// The value of the WHO_AM_I register.
+pub const WHO_AM_I: u8 = 0xbc;
+
+pub const REG_WHO_AM_I: u8 = 0x0f;
 pub const REG_AV_CONF: u8 = 0x10;
 pub const REG_CTRL1: u8 = 0x20;
+pub const REG_CTRL2: u8 = 0x21;
 pub const REG_STATUS: u8 = 0x27;
 pub const REG_HUMIDITY_OUT_L: u8 = 0x28;
 pub const REG_HUMIDITY_OUT_H: u8 = 0x29;
@@ -21,12 +28,74 @@ pub const REG_H1_T0_OUT: u8 = 0x3a;
 pub const REG_T0_OUT: u8 = 0x3c;
 pub const REG_T1_OUT: u8 = 0x3e;
 
+pub const CTRL1_PD: u8 = 0x80;
+pub const CTRL1_BDU: u8 = 0x04;
+pub const CTRL1_ODR_MASK: u8 = 0x03;
+pub const CTRL2_BOOT: u8 = 0x80;
+
+/// Set in the sub-address to read multiple registers in one transaction.
+pub const AUTO_INCREMENT: u8 = 0x80;
+
+/// The number of internal samples averaged into each humidity reading
+/// (the AVGH field of AV_CONF).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HumidityAveraging {
+    Samples4 = 0,
+    Samples8 = 1,
+    Samples16 = 2,
+    Samples32 = 3,
+    Samples64 = 4,
+    Samples128 = 5,
+    Samples256 = 6,
+    Samples512 = 7,
+}
+
+/// The number of internal samples averaged into each temperature reading
+/// (the AVGT field of AV_CONF).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TemperatureAveraging {
+    Samples2 = 0,
+    Samples4 = 1,
+    Samples8 = 2,
+    Samples16 = 3,
+    Samples32 = 4,
+    Samples64 = 5,
+    Samples128 = 6,
+    Samples256 = 7,
+}
+
+/// How often the humidity sensor takes a reading (the ODR field of
+/// CTRL_REG1). Slower rates use less power.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HumidityDataRate {
+    OneHz = 1,
+    SevenHz = 2,
+    TwelveHalfHz = 3,
+}
+
+/// The HTS221's per-chip calibration, as worked out from its calibration
+/// registers. Each reading is `raw * m + c`, where `raw` is the signed 16-bit
+/// output of the sensor.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct HumidityCalibration {
+    /// Degrees C per count.
+    pub temp_m: f64,
+    /// Degrees C at zero counts.
+    pub temp_c: f64,
+    /// Percent relative humidity per count.
+    pub hum_m: f64,
+    /// Percent relative humidity at zero counts.
+    pub hum_c: f64,
+}
+
 pub struct Hts221<T: I2CDevice + Sized> {
     i2cdev: T,
     temp_m: f64,
     temp_c: f64,
     hum_m: f64,
     hum_c: f64,
+    powered: bool,
+    rate: HumidityDataRate,
 }
 
 impl<T> Hts221<T>
@@ -35,55 +104,148 @@ where
 {
     /// Create a new pressure sensor handle for the given path/addr.
     /// Init sequence from https://github.com/RPi-Distro/RTIMULib
-    pub fn new(mut i2cdev: T) -> Result<Hts221<T>, T::Error> {
-        // Init
+    pub fn new(i2cdev: T) -> Result<Hts221<T>, T::Error> {
+        let mut hts221 = Hts221 {
+            i2cdev,
+            temp_m: 0.0,
+            temp_c: 0.0,
+            hum_m: 0.0,
+            hum_c: 0.0,
+            powered: false,
+            rate: HumidityDataRate::TwelveHalfHz,
+        };
+        hts221.init()?;
+        Ok(hts221)
+    }
+
+    /// Reboot the chip, reloading its calibration memory, and then
+    /// re-run the init sequence. Any averaging set with `set_averaging` is
+    /// lost, but the data rate is kept.
+    pub fn reset(&mut self) -> Result<(), T::Error> {
+        let ctrl2 = self.i2cdev.smbus_read_byte_data(REG_CTRL2)?;
+        self.i2cdev
+            .smbus_write_byte_data(REG_CTRL2, ctrl2 | CTRL2_BOOT)?;
+        // The BOOT bit clears itself once the reboot has finished
+        for _ in 0..10 {
+            if (self.i2cdev.smbus_read_byte_data(REG_CTRL2)? & CTRL2_BOOT) == 0 {
+                break;
+            }
+            ::std::thread::sleep(::std::time::Duration::from_millis(1));
+        }
+        self.init()
+    }
 
-        i2cdev.smbus_write_byte_data(REG_CTRL1, 0x87)?;
-        i2cdev.smbus_write_byte_data(REG_AV_CONF, 0x1b)?;
+    /// Configure the chip and load the calibration coefficients.
+    fn init(&mut self) -> Result<(), T::Error> {
+        // Init
+        self.i2cdev
+            .smbus_write_byte_data(REG_CTRL1, CTRL1_PD | CTRL1_BDU | self.rate as u8)?;
+        self.powered = true;
+        self.i2cdev.smbus_write_byte_data(REG_AV_CONF, 0x1b)?;
 
         // Get cal
         let mut buf = [0u8; 2];
-        buf[0] = i2cdev.smbus_read_byte_data(REG_T0_C_8)?;
-        buf[1] = i2cdev.smbus_read_byte_data(REG_T1_T0)? & 0x03;
+        buf[0] = self.i2cdev.smbus_read_byte_data(REG_T0_C_8)?;
+        buf[1] = self.i2cdev.smbus_read_byte_data(REG_T1_T0)? & 0x03;
         let t0 = f64::from(LittleEndian::read_i16(&buf)) / 8.0;
-        buf[0] = i2cdev.smbus_read_byte_data(REG_T1_C_8)?;
-        buf[1] = (i2cdev.smbus_read_byte_data(REG_T1_T0)? & 0x0C) >> 2;
+        buf[0] = self.i2cdev.smbus_read_byte_data(REG_T1_C_8)?;
+        buf[1] = (self.i2cdev.smbus_read_byte_data(REG_T1_T0)? & 0x0C) >> 2;
         let t1 = f64::from(LittleEndian::read_i16(&buf)) / 8.0;
 
-        buf[0] = i2cdev.smbus_read_byte_data(REG_T0_OUT)?;
-        buf[1] = i2cdev.smbus_read_byte_data(REG_T0_OUT + 1)?;
+        buf[0] = self.i2cdev.smbus_read_byte_data(REG_T0_OUT)?;
+        buf[1] = self.i2cdev.smbus_read_byte_data(REG_T0_OUT + 1)?;
         let t0_out = f64::from(LittleEndian::read_i16(&buf));
 
-        buf[0] = i2cdev.smbus_read_byte_data(REG_T1_OUT)?;
-        buf[1] = i2cdev.smbus_read_byte_data(REG_T1_OUT + 1)?;
+        buf[0] = self.i2cdev.smbus_read_byte_data(REG_T1_OUT)?;
+        buf[1] = self.i2cdev.smbus_read_byte_data(REG_T1_OUT + 1)?;
         let t1_out = f64::from(LittleEndian::read_i16(&buf));
 
-        buf[0] = i2cdev.smbus_read_byte_data(REG_H0_H_2)?;
+        buf[0] = self.i2cdev.smbus_read_byte_data(REG_H0_H_2)?;
         let h0 = f64::from(buf[0]) / 2.0;
 
-        buf[0] = i2cdev.smbus_read_byte_data(REG_H1_H_2)?;
+        buf[0] = self.i2cdev.smbus_read_byte_data(REG_H1_H_2)?;
         let h1 = f64::from(buf[0]) / 2.0;
 
-        buf[0] = i2cdev.smbus_read_byte_data(REG_H0_T0_OUT)?;
-        buf[1] = i2cdev.smbus_read_byte_data(REG_H0_T0_OUT + 1)?;
+        buf[0] = self.i2cdev.smbus_read_byte_data(REG_H0_T0_OUT)?;
+        buf[1] = self.i2cdev.smbus_read_byte_data(REG_H0_T0_OUT + 1)?;
         let h0_t0_out = f64::from(LittleEndian::read_i16(&buf));
 
-        buf[0] = i2cdev.smbus_read_byte_data(REG_H1_T0_OUT)?;
-        buf[1] = i2cdev.smbus_read_byte_data(REG_H1_T0_OUT + 1)?;
+        buf[0] = self.i2cdev.smbus_read_byte_data(REG_H1_T0_OUT)?;
+        buf[1] = self.i2cdev.smbus_read_byte_data(REG_H1_T0_OUT + 1)?;
         let h1_t0_out = f64::from(LittleEndian::read_i16(&buf));
 
-        let temp_m = (t1 - t0) / (t1_out - t0_out);
-        let temp_c = t0 - (temp_m * t0_out);
-        let hum_m = (h1 - h0) / (h1_t0_out - h0_t0_out);
-        let hum_c = h0 - (hum_m * h0_t0_out);
+        self.temp_m = (t1 - t0) / (t1_out - t0_out);
+        self.temp_c = t0 - (self.temp_m * t0_out);
+        self.hum_m = (h1 - h0) / (h1_t0_out - h0_t0_out);
+        self.hum_c = h0 - (self.hum_m * h0_t0_out);
 
-        Ok(Hts221 {
-            i2cdev,
-            temp_m,
-            temp_c,
-            hum_m,
-            hum_c,
-        })
+        Ok(())
+    }
+
+    /// Set how many samples the chip averages for each humidity and
+    /// temperature reading. More averaging gives less noisy readings, at the
+    /// cost of more power. The default is 32 humidity and 16 temperature
+    /// samples.
+    pub fn set_averaging(
+        &mut self,
+        humidity: HumidityAveraging,
+        temperature: TemperatureAveraging,
+    ) -> Result<(), T::Error> {
+        let av_conf = ((temperature as u8) << 3) | (humidity as u8);
+        self.i2cdev.smbus_write_byte_data(REG_AV_CONF, av_conf)
+    }
+
+    /// Set how often the chip takes a reading. This is kept across a
+    /// `reset()`, and doesn't power the chip up if it is powered down.
+    pub fn set_data_rate(&mut self, rate: HumidityDataRate) -> Result<(), T::Error> {
+        self.rate = rate;
+        let ctrl1 = self.i2cdev.smbus_read_byte_data(REG_CTRL1)?;
+        self.i2cdev
+            .smbus_write_byte_data(REG_CTRL1, (ctrl1 & !CTRL1_ODR_MASK) | rate as u8)
+    }
+
+    /// Put the chip into power-down mode, by clearing the PD bit in
+    /// CTRL1. Does nothing if the chip is already powered down.
+    pub fn power_down(&mut self) -> Result<(), T::Error> {
+        if self.powered {
+            let ctrl1 = self.i2cdev.smbus_read_byte_data(REG_CTRL1)?;
+            self.i2cdev
+                .smbus_write_byte_data(REG_CTRL1, ctrl1 & !CTRL1_PD)?;
+            self.powered = false;
+        }
+        Ok(())
+    }
+
+    /// Bring the chip out of power-down mode, by setting the PD bit in
+    /// CTRL1. Does nothing if the chip is already powered up.
+    pub fn power_up(&mut self) -> Result<(), T::Error> {
+        if !self.powered {
+            let ctrl1 = self.i2cdev.smbus_read_byte_data(REG_CTRL1)?;
+            self.i2cdev
+                .smbus_write_byte_data(REG_CTRL1, ctrl1 | CTRL1_PD)?;
+            self.powered = true;
+        }
+        Ok(())
+    }
+
+    /// Read the WHO_AM_I register, which identifies the chip.
+    pub fn who_am_i(&mut self) -> Result<u8, T::Error> {
+        self.i2cdev.smbus_read_byte_data(REG_WHO_AM_I)
+    }
+
+    /// Returns the calibration read from the chip by `new` or `reset`.
+    pub fn calibration(&self) -> HumidityCalibration {
+        HumidityCalibration {
+            temp_m: self.temp_m,
+            temp_c: self.temp_c,
+            hum_m: self.hum_m,
+            hum_c: self.hum_c,
+        }
+    }
+
+    /// Is the chip powered up?
+    pub fn is_powered(&self) -> bool {
+        self.powered
     }
 
     /// Obtain the status bitfield from the chip.
@@ -95,6 +257,10 @@ where
         let mut buf = [0u8; 2];
         buf[0] = self.i2cdev.smbus_read_byte_data(REG_HUMIDITY_OUT_L)?;
         buf[1] = self.i2cdev.smbus_read_byte_data(REG_HUMIDITY_OUT_H)?;
+        debug!(
+            "HTS221 HUMIDITY_OUT (0x{:02x}) = {:02x?}",
+            REG_HUMIDITY_OUT_L, buf
+        );
         Ok(LittleEndian::read_i16(&buf))
     }
 
@@ -107,6 +273,7 @@ where
         let mut buf = [0u8; 2];
         buf[0] = self.i2cdev.smbus_read_byte_data(REG_TEMP_OUT_L)?;
         buf[1] = self.i2cdev.smbus_read_byte_data(REG_TEMP_OUT_H)?;
+        debug!("HTS221 TEMP_OUT (0x{:02x}) = {:02x?}", REG_TEMP_OUT_L, buf);
         Ok(LittleEndian::read_i16(&buf))
     }
 
@@ -114,4 +281,50 @@ where
         self.get_temperature()
             .and_then(|c| Ok((f64::from(c) * self.temp_m) + self.temp_c))
     }
+
+    /// Obtain the temperature in deg C and the relative humidity in percent,
+    /// reading all four output registers in a single transaction so that
+    /// they always come from the same conversion.
+    pub fn get_temperature_and_humidity(&mut self) -> Result<(f64, f64), T::Error> {
+        let buf = self
+            .i2cdev
+            .smbus_read_i2c_block_data(REG_HUMIDITY_OUT_L | AUTO_INCREMENT, 4)?;
+        debug!(
+            "HTS221 HUMIDITY_OUT (0x{:02x}) = {:02x?}",
+            REG_HUMIDITY_OUT_L, buf
+        );
+        let humidity = f64::from(LittleEndian::read_i16(&buf[0..2]));
+        let temperature = f64::from(LittleEndian::read_i16(&buf[2..4]));
+        Ok((
+            (temperature * self.temp_m) + self.temp_c,
+            (humidity * self.hum_m) + self.hum_c,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mock::MockI2CDevice;
+
+    #[test]
+    fn check_data_rate() {
+        let mut hts221 = Hts221::new(MockI2CDevice::hts221()).unwrap();
+        assert_eq!(hts221.i2cdev.register(REG_CTRL1), 0x87);
+        hts221.set_data_rate(HumidityDataRate::OneHz).unwrap();
+        assert_eq!(hts221.i2cdev.register(REG_CTRL1), 0x85);
+        hts221.power_down().unwrap();
+        hts221.set_data_rate(HumidityDataRate::SevenHz).unwrap();
+        assert_eq!(hts221.i2cdev.register(REG_CTRL1), 0x06);
+    }
+
+    #[test]
+    fn check_calibration() {
+        let hts221 = Hts221::new(MockI2CDevice::hts221()).unwrap();
+        let cal = hts221.calibration();
+        assert!((cal.temp_m - 0.01).abs() < 1e-9);
+        assert!((cal.temp_c - 20.0).abs() < 1e-9);
+        assert!((cal.hum_m - 0.01).abs() < 1e-9);
+        assert!((cal.hum_c - 20.0).abs() < 1e-9);
+    }
 }
@@ -0,0 +1,60 @@
+//! * Setting how long the kernel waits for a stuck I2C bus
+//!
+//! If a faulty chip or a corroded connector holds the bus low, a transfer
+//! can wait a long time for it to be released. The `I2C_TIMEOUT` ioctl sets
+//! how long the kernel waits before giving up with an error. It applies to
+//! the whole bus (the adapter), not just the device it is set through.
+//!
+//! `i2cdev` knows the ioctl number but doesn't make it public, so it is
+//! called here directly.
+
+use i2cdev::linux::LinuxI2CError;
+use std::os::raw::{c_int, c_ulong};
+use std::os::unix::io::AsRawFd;
+use std::time::Duration;
+
+/// The timeout set when a `SenseHat` is created, unless the builder is
+/// given another.
+pub(crate) const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// From `linux/i2c-dev.h`.
+const I2C_TIMEOUT: c_ulong = 0x0702;
+
+extern "C" {
+    fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+}
+
+/// Sets the timeout for the bus `device` is on.
+pub(crate) fn set_timeout<F>(device: &F, timeout: Duration) -> Result<(), LinuxI2CError>
+where
+    F: AsRawFd,
+{
+    // The ioctl's argument is an int, passed by value.
+    let units = timeout_units(timeout);
+    if unsafe { ioctl(device.as_raw_fd(), I2C_TIMEOUT, units) } < 0 {
+        return Err(LinuxI2CError::Io(::std::io::Error::last_os_error()));
+    }
+    Ok(())
+}
+
+/// The kernel takes the timeout in units of 10 ms. This rounds up, so that
+/// a short timeout doesn't become no time at all.
+fn timeout_units(timeout: Duration) -> c_int {
+    let units = timeout.as_nanos().div_ceil(10_000_000).max(1);
+    units.min(c_int::MAX as u128) as c_int
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn check_timeout_units() {
+        assert_eq!(timeout_units(Duration::from_secs(1)), 100);
+        assert_eq!(timeout_units(Duration::from_millis(25)), 3);
+        assert_eq!(timeout_units(Duration::from_millis(0)), 1);
+        assert_eq!(timeout_units(Duration::from_secs(u64::MAX)), c_int::MAX);
+    }
+}
+
+// End of file
@@ -0,0 +1,54 @@
+//! * The interface to an IMU driver
+//!
+//! `SenseHat` talks to the LSM9DS1 through this trait, so that the driver
+//! (currently the `RTIMULib` wrapper) can be swapped for another one.
+
+use super::{ImuData, SenseHatResult};
+use std::time::Duration;
+
+/// Which of the IMU's sensors are in use.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum ImuMode {
+    Fusion,
+    CompassOnly,
+    GyroOnly,
+    AccelOnly,
+}
+
+/// An IMU driver.
+pub(crate) trait ImuBackend {
+    /// Make the IMU do some work. When this function returns true, the IMU
+    /// has data we can fetch with `get_imu_data()`.
+    fn imu_read(&mut self) -> bool;
+
+    /// How often `imu_read()` should be called, to keep up with the
+    /// sensors.
+    fn poll_interval(&self) -> Duration;
+
+    /// Returns the data from the last successful `imu_read()`.
+    fn get_imu_data(&mut self) -> SenseHatResult<ImuData>;
+
+    /// Use the gyroscope, accelerometer and magnetometer together.
+    fn set_fusion(&mut self);
+
+    /// Use only the magnetometer.
+    fn set_compass_only(&mut self);
+
+    /// Use only the gyroscope.
+    fn set_gyro_only(&mut self);
+
+    /// Use only the accelerometer.
+    fn set_accel_only(&mut self);
+
+    /// Switches to the given mode.
+    fn set_mode(&mut self, mode: ImuMode) {
+        match mode {
+            ImuMode::Fusion => self.set_fusion(),
+            ImuMode::CompassOnly => self.set_compass_only(),
+            ImuMode::GyroOnly => self.set_gyro_only(),
+            ImuMode::AccelOnly => self.set_accel_only(),
+        }
+    }
+}
+
+// End of file
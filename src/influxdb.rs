@@ -0,0 +1,85 @@
+//! * Formatting of readings as InfluxDB line protocol
+//!
+//! See <https://docs.influxdata.com/influxdb/latest/reference/syntax/line-protocol/>.
+//! Temperatures are in degrees Celsius, pressures in hectopascals and
+//! humidity in percent.
+
+use super::{Environment, Reading};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+impl Environment {
+    /// Formats the readings as a line of InfluxDB line protocol, e.g.
+    /// `sensehat temperature=21.3,humidity=45.2,pressure=1012.4 1546300800000000000`,
+    /// with the given measurement name and timestamp. Readings that are
+    /// `None` are left out, and if they all are there's nothing to send, so
+    /// this returns `None`.
+    pub fn to_line_protocol(&self, measurement: &str, timestamp: SystemTime) -> Option<String> {
+        let fields: Vec<String> = [
+            ("temperature", self.temperature().map(|t| t.as_celsius())),
+            ("humidity", self.humidity.map(|h| h.as_percent())),
+            ("pressure", self.pressure.map(|p| p.as_hectopascals())),
+        ]
+        .iter()
+        .filter_map(|&(name, value)| value.map(|value| format!("{}={}", name, value)))
+        .collect();
+        if fields.is_empty() {
+            return None;
+        }
+        let nanos = timestamp
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() * 1_000_000_000 + u64::from(d.subsec_nanos()))
+            .unwrap_or(0);
+        Some(format!(
+            "{} {} {}",
+            escape_measurement(measurement),
+            fields.join(","),
+            nanos
+        ))
+    }
+}
+
+impl Reading {
+    /// Formats the environmental readings as a line of InfluxDB line
+    /// protocol, timestamped with when the reading was taken. See
+    /// `Environment::to_line_protocol`.
+    pub fn to_line_protocol(&self, measurement: &str) -> Option<String> {
+        self.environment
+            .to_line_protocol(measurement, self.timestamp)
+    }
+}
+
+/// Measurement names must have their commas and spaces escaped.
+fn escape_measurement(measurement: &str) -> String {
+    measurement.replace(',', "\\,").replace(' ', "\\ ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+    use {Pressure, RelativeHumidity, Temperature};
+
+    #[test]
+    fn check_line_protocol() {
+        let env = Environment {
+            temperature_from_humidity: Some(Temperature::from_celsius(21.5)),
+            temperature_from_pressure: None,
+            pressure: Some(Pressure::from_hectopascals(1012.25)),
+            humidity: Some(RelativeHumidity::from_percent(45.0)),
+        };
+        let time = UNIX_EPOCH + Duration::new(1, 5);
+        assert_eq!(
+            env.to_line_protocol("sense hat", time).unwrap(),
+            "sense\\ hat temperature=21.5,humidity=45,pressure=1012.25 1000000005"
+        );
+        let env = Environment {
+            temperature_from_humidity: None,
+            temperature_from_pressure: None,
+            pressure: None,
+            humidity: None,
+        };
+        assert!(env.to_line_protocol("sensehat", time).is_none());
+    }
+}
+
+// End of file
@@ -0,0 +1,268 @@
+//! * Driver for the Sense HAT joystick
+//!
+//! The joystick appears as a Linux input device,
+//! `/dev/input/eventN`, which sends a key event each time it is pressed,
+//! held or released.
+//!
+//! The device is opened in non-blocking mode, so `Joystick` can be used from
+//! an event loop: `try_read_event` never waits, and the file descriptor from
+//! `as_raw_fd` can be registered with something like tokio's `AsyncFd`.
+
+use byteorder::{ByteOrder, NativeEndian};
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::Path;
+use std::time::Duration;
+
+/// The name the kernel gives the joystick's input device.
+const DEVICE_NAME: &str = "Raspberry Pi Sense HAT Joystick";
+
+/// `ENODEV` from `errno.h`, which reads give once the device has gone.
+const ENODEV: i32 = 19;
+
+/// `O_NONBLOCK` from `fcntl.h`, which has this value on every Linux
+/// architecture the Raspberry Pi runs.
+const O_NONBLOCK: i32 = 0o4000;
+
+/// `struct input_event` is a `struct timeval` (two `long`s) followed by a
+/// `u16` type, a `u16` code and an `i32` value.
+const LONG_SIZE: usize = ::std::mem::size_of::<isize>();
+const EVENT_SIZE: usize = 2 * LONG_SIZE + 8;
+
+const EV_KEY: u16 = 0x01;
+const KEY_ENTER: u16 = 28;
+const KEY_UP: u16 = 103;
+const KEY_LEFT: u16 = 105;
+const KEY_RIGHT: u16 = 106;
+const KEY_DOWN: u16 = 108;
+
+/// Which way the joystick was pushed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+    /// The joystick was pushed in, like a button.
+    Middle,
+}
+
+/// What happened to the joystick.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Action {
+    Pressed,
+    Released,
+    /// The joystick is still being held; this repeats until it is released.
+    Held,
+}
+
+/// A single joystick event.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct JoystickEvent {
+    /// When the event happened, as reported by the kernel.
+    pub timestamp: Duration,
+    pub direction: Direction,
+    pub action: Action,
+}
+
+/// The Sense HAT joystick.
+pub struct Joystick {
+    file: File,
+}
+
+impl Joystick {
+    /// Opens the joystick at the given input device, e.g. `/dev/input/event0`.
+    pub fn open<P>(path: P) -> io::Result<Joystick>
+    where
+        P: AsRef<Path>,
+    {
+        let file = ::std::fs::OpenOptions::new()
+            .read(true)
+            .custom_flags(O_NONBLOCK)
+            .open(path)?;
+        Ok(Joystick { file })
+    }
+
+    /// Returns the next joystick event if there is one, without waiting.
+    ///
+    /// If the input device has gone away, because the Sense HAT was
+    /// unplugged or the input subsystem was reset, this returns an error of
+    /// kind `ErrorKind::NotConnected`. It won't come back: find the device
+    /// again and open a new `Joystick`.
+    pub fn try_read_event(&mut self) -> io::Result<Option<JoystickEvent>> {
+        let mut buf = [0u8; EVENT_SIZE];
+        loop {
+            match self.file.read(&mut buf) {
+                Ok(n) if n == EVENT_SIZE => {
+                    // Skip the sync events between key events
+                    if let Some(event) = parse_event(&buf) {
+                        return Ok(Some(event));
+                    }
+                }
+                Ok(_) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "short read from joystick",
+                    ))
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(read_error(e)),
+            }
+        }
+    }
+
+    /// Waits for the next joystick event. This polls the device every
+    /// 10 ms. Like `try_read_event`, it returns an error of kind
+    /// `ErrorKind::NotConnected` if the device goes away, rather than
+    /// waiting forever.
+    pub fn read_event(&mut self) -> io::Result<JoystickEvent> {
+        loop {
+            if let Some(event) = self.try_read_event()? {
+                return Ok(event);
+            }
+            ::std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+impl AsRawFd for Joystick {
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+/// Finds the joystick's input device, by looking for the one with the right
+/// name in `sysfs` (normally `/sys/class/input`).
+pub(crate) fn detect_joystick(sysfs: &Path) -> Option<String> {
+    for entry in ::std::fs::read_dir(sysfs).ok()? {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(_) => continue,
+        };
+        let name = match entry.file_name().into_string() {
+            Ok(ref name) if name.starts_with("event") => name.clone(),
+            _ => continue,
+        };
+        let path = entry.path().join("device").join("name");
+        if let Ok(device_name) = ::std::fs::read_to_string(path) {
+            if device_name.trim() == DEVICE_NAME {
+                return Some(format!("/dev/input/{}", name));
+            }
+        }
+    }
+    None
+}
+
+/// Turns the `ENODEV` from reading a device which has gone into a
+/// `NotConnected` error, which is easier to check for.
+fn read_error(err: io::Error) -> io::Error {
+    if err.raw_os_error() == Some(ENODEV) {
+        io::Error::new(io::ErrorKind::NotConnected, "joystick disconnected")
+    } else {
+        err
+    }
+}
+
+/// Decodes a `struct input_event`. Returns `None` if it isn't a joystick key
+/// event.
+fn parse_event(buf: &[u8; EVENT_SIZE]) -> Option<JoystickEvent> {
+    let (secs, usecs) = if LONG_SIZE == 8 {
+        (
+            NativeEndian::read_i64(&buf[0..8]),
+            NativeEndian::read_i64(&buf[8..16]),
+        )
+    } else {
+        (
+            i64::from(NativeEndian::read_i32(&buf[0..4])),
+            i64::from(NativeEndian::read_i32(&buf[4..8])),
+        )
+    };
+    let rest = &buf[2 * LONG_SIZE..];
+    if NativeEndian::read_u16(&rest[0..2]) != EV_KEY {
+        return None;
+    }
+    let direction = match NativeEndian::read_u16(&rest[2..4]) {
+        KEY_UP => Direction::Up,
+        KEY_DOWN => Direction::Down,
+        KEY_LEFT => Direction::Left,
+        KEY_RIGHT => Direction::Right,
+        KEY_ENTER => Direction::Middle,
+        _ => return None,
+    };
+    let action = match NativeEndian::read_i32(&rest[4..8]) {
+        0 => Action::Released,
+        1 => Action::Pressed,
+        2 => Action::Held,
+        _ => return None,
+    };
+    Some(JoystickEvent {
+        timestamp: Duration::new(secs.max(0) as u64, (usecs.max(0) as u32) * 1000),
+        direction,
+        action,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn event(event_type: u16, code: u16, value: i32) -> [u8; EVENT_SIZE] {
+        let mut buf = [0u8; EVENT_SIZE];
+        if LONG_SIZE == 8 {
+            NativeEndian::write_i64(&mut buf[0..8], 12);
+            NativeEndian::write_i64(&mut buf[8..16], 500);
+        } else {
+            NativeEndian::write_i32(&mut buf[0..4], 12);
+            NativeEndian::write_i32(&mut buf[4..8], 500);
+        }
+        let rest = &mut buf[2 * LONG_SIZE..];
+        NativeEndian::write_u16(&mut rest[0..2], event_type);
+        NativeEndian::write_u16(&mut rest[2..4], code);
+        NativeEndian::write_i32(&mut rest[4..8], value);
+        buf
+    }
+
+    #[test]
+    fn check_parse_event() {
+        let parsed = parse_event(&event(EV_KEY, KEY_LEFT, 1)).unwrap();
+        assert_eq!(parsed.direction, Direction::Left);
+        assert_eq!(parsed.action, Action::Pressed);
+        assert_eq!(parsed.timestamp, Duration::new(12, 500_000));
+        let parsed = parse_event(&event(EV_KEY, KEY_ENTER, 2)).unwrap();
+        assert_eq!(parsed.direction, Direction::Middle);
+        assert_eq!(parsed.action, Action::Held);
+        // EV_SYN
+        assert!(parse_event(&event(0, 0, 0)).is_none());
+    }
+
+    #[test]
+    fn check_read_error() {
+        let gone = read_error(io::Error::from_raw_os_error(ENODEV));
+        assert_eq!(gone.kind(), io::ErrorKind::NotConnected);
+        // EIO is passed through
+        let other = read_error(io::Error::from_raw_os_error(5));
+        assert_eq!(other.raw_os_error(), Some(5));
+    }
+
+    #[test]
+    fn check_detect_joystick() {
+        let sysfs = ::std::env::temp_dir().join(format!("sensehat-input-{}", ::std::process::id()));
+        for &(event, name) in &[("event0", "gpio-keys"), ("event3", DEVICE_NAME)] {
+            let device = sysfs.join(event).join("device");
+            ::std::fs::create_dir_all(&device).unwrap();
+            ::std::fs::write(device.join("name"), format!("{}\n", name)).unwrap();
+        }
+        // Not an event device, even though it has the right name
+        ::std::fs::create_dir_all(sysfs.join("input1")).unwrap();
+        ::std::fs::write(sysfs.join("input1").join("name"), DEVICE_NAME).unwrap();
+        let found = detect_joystick(&sysfs);
+        ::std::fs::remove_dir_all(&sysfs).unwrap();
+        assert_eq!(found, Some(String::from("/dev/input/event3")));
+        assert_eq!(detect_joystick(&sysfs), None);
+    }
+}
+
+// End of file
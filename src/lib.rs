@@ -18,11 +18,9 @@
 //!
 //! * Humidity and Temperature Sensor (an HTS221)
 //! * Pressure and Temperature Sensor (a LPS25H)
-//! * Gyroscope (an LSM9DS1, requires the RTIMU library)
+//! * Gyroscope (an LSM9DS1, requires the RTIMU library, or the simpler
+//!   pure-Rust driver with the `native-imu` feature)
 //! * LED matrix (partial support for scrolling text only)
-//!
-//! ## Currently unsupported components:
-//!
 //! * Joystick
 //!
 //! ## Example use
@@ -34,6 +32,29 @@
 //!     hat.text("Hi!", Colour::RED, Colour::WHITE).unwrap();
 //! }
 //! ```
+//!
+//! ## More than one Sense HAT
+//!
+//! Each `SenseHat` owns its own I2C devices, framebuffer and IMU driver, so
+//! several can be used at once. Give each one its own bus and joystick (and
+//! framebuffer, with `framebuffer`) using `SenseHatBuilder`:
+//!
+//! ```no_run
+//! use sensehat::SenseHatBuilder;
+//! let first = SenseHatBuilder::new().build().unwrap();
+//! let second = SenseHatBuilder::new()
+//!     .i2c_bus("/dev/i2c-3")
+//!     .imu_settings("/home/pi/.config/sense_hat/second.ini")
+//!     .joystick_path("/dev/input/event5")
+//!     .build()
+//!     .unwrap();
+//! ```
+//!
+//! `RTIMULib` doesn't use the bus you give: it finds the IMU on the bus
+//! named by `I2CBus` in its settings file, and the default settings file
+//! (`RTIMULib.ini` in the current directory) is shared. So for a second HAT,
+//! pass a settings file with its own `I2CBus`, as above. The `native-imu`
+//! driver uses the same bus as the other sensors.
 
 extern crate byteorder;
 extern crate i2cdev;
@@ -47,19 +68,78 @@ extern crate libc;
 #[cfg(feature = "led-matrix")]
 extern crate sensehat_screen;
 
+#[cfg(feature = "serde")]
+extern crate serde;
+
+/// Writes a diagnostic message to stderr with the `logging` feature. Without
+/// it, the arguments are still type-checked, but nothing is formatted.
+#[cfg(feature = "logging")]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        eprintln!("sensehat: {}", format_args!($($arg)*))
+    };
+}
+
+#[cfg(not(feature = "logging"))]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        if false {
+            let _ = format_args!($($arg)*);
+        }
+    };
+}
+
+#[cfg(feature = "async")]
+mod async_hat;
+mod builder;
+#[cfg(feature = "led-matrix")]
+mod digits;
+#[cfg(feature = "led-matrix")]
+mod gamma;
 mod hts221;
+mod i2c_timeout;
+mod imu;
+#[cfg(feature = "influxdb")]
+mod influxdb;
+mod joystick;
 mod lps25h;
+pub mod mock;
+mod pressure_trend;
+mod retry;
 mod rh;
+#[cfg(feature = "led-matrix")]
+mod screen_info;
+mod self_test;
+#[cfg(feature = "serde")]
+mod serde_units;
+mod shared_bus;
+mod weather;
 
 use std::error::Error;
 use std::fmt::Display;
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "async")]
+pub use async_hat::{AsyncSenseHat, JoystickStream};
+pub use builder::SenseHatBuilder;
+pub use hts221::{HumidityAveraging, HumidityCalibration, HumidityDataRate, TemperatureAveraging};
+pub use joystick::{Action, Direction, Joystick, JoystickEvent};
+pub use lps25h::{PressureAveraging, PressureDataRate};
 pub use measurements::Angle;
+pub use measurements::Length;
 pub use measurements::Pressure;
 pub use measurements::Temperature;
+pub use pressure_trend::{PressureTrend, Tendency};
+pub use retry::RetryPolicy;
 pub use rh::RelativeHumidity;
+pub use self_test::{SelfTestReport, SelfTestResult};
+pub use shared_bus::{SharedI2CBus, SharedI2CDevice};
 
+use i2cdev::core::I2CDevice;
 use i2cdev::linux::{LinuxI2CDevice, LinuxI2CError};
+use imu::{ImuBackend, ImuMode};
 
 #[cfg(feature = "rtimu")]
 mod lsm9ds1;
@@ -69,25 +149,187 @@ mod lsm9ds1_dummy;
 #[cfg(not(feature = "rtimu"))]
 use lsm9ds1_dummy as lsm9ds1;
 
+#[cfg(all(feature = "native-imu", not(feature = "rtimu")))]
+mod lsm9ds1_native;
+
 #[cfg(feature = "led-matrix")]
 use sensehat_screen::color::PixelColor;
 
+#[cfg(feature = "led-matrix")]
+pub use sensehat_screen::fonts::{FontCollection, FontUnicode};
+#[cfg(feature = "led-matrix")]
+pub use sensehat_screen::PixelFrame;
+
 /// Represents an orientation from the IMU.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Orientation {
+    #[cfg_attr(feature = "serde", serde(with = "serde_units"))]
     pub roll: Angle,
+    #[cfg_attr(feature = "serde", serde(with = "serde_units"))]
     pub pitch: Angle,
+    #[cfg_attr(feature = "serde", serde(with = "serde_units"))]
     pub yaw: Angle,
 }
 
+impl Orientation {
+    /// Returns `(roll, pitch, yaw)` in degrees.
+    pub fn as_degrees(&self) -> (f64, f64, f64) {
+        (
+            self.roll.as_degrees(),
+            self.pitch.as_degrees(),
+            self.yaw.as_degrees(),
+        )
+    }
+
+    /// Returns `(roll, pitch, yaw)` in radians.
+    pub fn as_radians(&self) -> (f64, f64, f64) {
+        (
+            self.roll.as_radians(),
+            self.pitch.as_radians(),
+            self.yaw.as_radians(),
+        )
+    }
+
+    /// Returns how far each angle has turned from `other` to `self`, the
+    /// shortest way round, from -180 to 180 degrees. Going from 179 degrees
+    /// to -179 degrees is a difference of 2 degrees, not -358.
+    pub fn difference(&self, other: &Orientation) -> Orientation {
+        Orientation {
+            roll: angle_difference(self.roll, other.roll),
+            pitch: angle_difference(self.pitch, other.pitch),
+            yaw: angle_difference(self.yaw, other.yaw),
+        }
+    }
+
+    /// Returns the orientation as a unit quaternion, `[w, x, y, z]`. The
+    /// angles are applied in the same order as RTIMULib: yaw about z, then
+    /// pitch about the new y, then roll about the new x. Unlike the angles,
+    /// a quaternion has no gimbal lock, and changes smoothly as the board
+    /// turns, so it is better for feeding into filters or 3D engines.
+    pub fn to_quaternion(&self) -> [f64; 4] {
+        let (sin_r, cos_r) = (self.roll.as_radians() / 2.0).sin_cos();
+        let (sin_p, cos_p) = (self.pitch.as_radians() / 2.0).sin_cos();
+        let (sin_y, cos_y) = (self.yaw.as_radians() / 2.0).sin_cos();
+        [
+            cos_r * cos_p * cos_y + sin_r * sin_p * sin_y,
+            sin_r * cos_p * cos_y - cos_r * sin_p * sin_y,
+            cos_r * sin_p * cos_y + sin_r * cos_p * sin_y,
+            cos_r * cos_p * sin_y - sin_r * sin_p * cos_y,
+        ]
+    }
+}
+
+impl Display for Orientation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (roll, pitch, yaw) = self.as_degrees();
+        write!(f, "roll={:.1}°, pitch={:.1}°, yaw={:.1}°", roll, pitch, yaw)
+    }
+}
+
 /// Represents a 3D vector.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Vector3D {
     pub x: f64,
     pub y: f64,
     pub z: f64,
 }
 
+impl Vector3D {
+    /// Returns the length of the vector.
+    pub fn magnitude(&self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    /// Returns a vector in the same direction with a length of one. A zero
+    /// vector has no direction, so gives a vector of NaNs.
+    pub fn normalized(&self) -> Vector3D {
+        *self * (1.0 / self.magnitude())
+    }
+
+    /// Returns the dot (scalar) product of two vectors.
+    pub fn dot(&self, other: &Vector3D) -> f64 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    /// Multiplies each component by the matching component of `other`.
+    fn scaled(&self, other: &Vector3D) -> Vector3D {
+        Vector3D {
+            x: self.x * other.x,
+            y: self.y * other.y,
+            z: self.z * other.z,
+        }
+    }
+
+    /// Returns the cross (vector) product of two vectors.
+    pub fn cross(&self, other: &Vector3D) -> Vector3D {
+        Vector3D {
+            x: self.y * other.z - self.z * other.y,
+            y: self.z * other.x - self.x * other.z,
+            z: self.x * other.y - self.y * other.x,
+        }
+    }
+}
+
+impl ::std::ops::Add for Vector3D {
+    type Output = Vector3D;
+
+    fn add(self, other: Vector3D) -> Vector3D {
+        Vector3D {
+            x: self.x + other.x,
+            y: self.y + other.y,
+            z: self.z + other.z,
+        }
+    }
+}
+
+impl ::std::ops::Sub for Vector3D {
+    type Output = Vector3D;
+
+    fn sub(self, other: Vector3D) -> Vector3D {
+        Vector3D {
+            x: self.x - other.x,
+            y: self.y - other.y,
+            z: self.z - other.z,
+        }
+    }
+}
+
+impl ::std::ops::Mul<f64> for Vector3D {
+    type Output = Vector3D;
+
+    fn mul(self, scale: f64) -> Vector3D {
+        Vector3D {
+            x: self.x * scale,
+            y: self.y * scale,
+            z: self.z * scale,
+        }
+    }
+}
+
+/// How far the LED matrix picture is turned clockwise, for when the Sense
+/// HAT is mounted sideways or upside down. See `SenseHat::set_rotation`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Rotation {
+    Deg0,
+    Deg90,
+    Deg180,
+    Deg270,
+}
+
+impl Rotation {
+    /// Returns the rotation which undoes this one.
+    #[cfg(feature = "led-matrix")]
+    fn inverse(self) -> Rotation {
+        match self {
+            Rotation::Deg90 => Rotation::Deg270,
+            Rotation::Deg270 => Rotation::Deg90,
+            r => r,
+        }
+    }
+}
+
 /// Represents an RGB colour.
 #[cfg(feature = "led-matrix")]
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -98,8 +340,158 @@ pub struct Colour(PixelColor);
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Fps(pub u8);
 
+/// The size and pixel depth a framebuffer device reports. The Sense HAT's
+/// LED matrix is 8 by 8 pixels, at 16 bits per pixel (RGB565).
+#[cfg(feature = "led-matrix")]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FramebufferFormat {
+    pub width: u32,
+    pub height: u32,
+    pub bits_per_pixel: u32,
+}
+
+/// The hard-iron calibration for the magnetometer: the smallest and largest
+/// reading seen on each axis while the Sense HAT was rotated, in microteslas.
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CompassCalibration {
+    pub min: Vector3D,
+    pub max: Vector3D,
+}
+
+impl CompassCalibration {
+    /// The hard-iron offset, which is the midpoint of the readings on each
+    /// axis. It is subtracted from the raw magnetometer readings.
+    pub fn offset(&self) -> Vector3D {
+        (self.min + self.max) * 0.5
+    }
+
+    /// Widens the calibration to include the given reading.
+    fn include(self, c: Vector3D) -> CompassCalibration {
+        CompassCalibration {
+            min: Vector3D {
+                x: self.min.x.min(c.x),
+                y: self.min.y.min(c.y),
+                z: self.min.z.min(c.z),
+            },
+            max: Vector3D {
+                x: self.max.x.max(c.x),
+                y: self.max.y.max(c.y),
+                z: self.max.z.max(c.z),
+            },
+        }
+    }
+}
+
+/// All the data from a single IMU sample.
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ImuReading {
+    /// When the sample was taken, in microseconds.
+    pub timestamp: u64,
+    /// The fused orientation, from all three sensors.
+    pub fusion_pose: Option<Orientation>,
+    /// The angular velocity, in radians per second.
+    pub gyro: Option<Vector3D>,
+    /// The acceleration, in Gs.
+    pub accel: Option<Vector3D>,
+    /// The magnetic field, in microteslas.
+    pub compass: Option<Vector3D>,
+}
+
+/// All the environmental readings, from `get_environment`. Each is `None` if
+/// its sensor had no reading ready.
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Environment {
+    /// The temperature from the humidity sensor.
+    #[cfg_attr(feature = "serde", serde(with = "serde_units::option"))]
+    pub temperature_from_humidity: Option<Temperature>,
+    /// The temperature from the pressure sensor.
+    #[cfg_attr(feature = "serde", serde(with = "serde_units::option"))]
+    pub temperature_from_pressure: Option<Temperature>,
+    #[cfg_attr(feature = "serde", serde(with = "serde_units::option"))]
+    pub pressure: Option<Pressure>,
+    pub humidity: Option<RelativeHumidity>,
+}
+
+impl Environment {
+    /// The best available temperature: the humidity sensor's if it had a
+    /// reading, otherwise the pressure sensor's.
+    pub fn temperature(&self) -> Option<Temperature> {
+        self.temperature_from_humidity
+            .or(self.temperature_from_pressure)
+    }
+}
+
+/// A timestamped sample of every sensor, from `get_reading` or
+/// `spawn_sampler`.
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Reading {
+    /// When the sample was taken.
+    pub timestamp: ::std::time::SystemTime,
+    pub environment: Environment,
+    /// The IMU sample, or `None` if the IMU had no data.
+    pub imu: Option<ImuReading>,
+}
+
+/// An endless iterator of environmental readings, one every `interval`,
+/// from `SenseHat::environment_stream`.
+pub struct EnvironmentStream<'s, 'a: 's, D>
+where
+    D: I2CDevice + 's,
+{
+    hat: &'s mut SenseHat<'a, D>,
+    ticker: Ticker,
+}
+
+impl<'s, 'a, D> Iterator for EnvironmentStream<'s, 'a, D>
+where
+    D: I2CDevice,
+    SenseHatError: From<D::Error>,
+{
+    type Item = SenseHatResult<Environment>;
+
+    fn next(&mut self) -> Option<SenseHatResult<Environment>> {
+        self.ticker.wait();
+        Some(self.hat.get_environment())
+    }
+}
+
+/// Waits for fixed-rate ticks. The first tick is straight away, and each
+/// one after is timed from the one before, so the time spent between them
+/// doesn't make the rate drift.
+struct Ticker {
+    interval: ::std::time::Duration,
+    next: Option<::std::time::Instant>,
+}
+
+impl Ticker {
+    fn new(interval: ::std::time::Duration) -> Ticker {
+        Ticker {
+            interval,
+            next: None,
+        }
+    }
+
+    /// Sleeps until the next tick is due.
+    fn wait(&mut self) {
+        let now = ::std::time::Instant::now();
+        let next = match self.next {
+            Some(next) if next > now => {
+                ::std::thread::sleep(next - now);
+                next
+            }
+            // Running behind (or just started), so don't try to catch up
+            _ => now,
+        };
+        self.next = Some(next + self.interval);
+    }
+}
+
 /// A collection of all the data from the IMU.
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 struct ImuData {
     timestamp: u64,
     fusion_pose: Option<Orientation>,
@@ -112,44 +504,221 @@ struct ImuData {
 }
 
 /// Represents the Sense HAT itself.
-pub struct SenseHat<'a> {
+pub struct SenseHat<'a, D = LinuxI2CDevice>
+where
+    D: I2CDevice,
+{
     /// LPS25H pressure sensor.
-    pressure_chip: lps25h::Lps25h<LinuxI2CDevice>,
+    pressure_chip: lps25h::Lps25h<D>,
     /// HTS221 humidity sensor.
-    humidity_chip: hts221::Hts221<LinuxI2CDevice>,
+    humidity_chip: hts221::Hts221<D>,
     /// LSM9DS1 IMU device.
-    accelerometer_chip: lsm9ds1::Lsm9ds1<'a>,
+    accelerometer_chip: Box<dyn ImuBackend + 'a>,
     /// Cached accelerometer data.
     data: ImuData,
+    /// The sensors last enabled on the IMU, so they're only changed when
+    /// they need to be.
+    imu_mode: Option<ImuMode>,
+    /// Subtracted from temperature readings, in degrees C.
+    temperature_offset: f64,
+    /// How to retry sensor reads after transient I2C errors.
+    retry: RetryPolicy,
+    /// Recent pressure readings, once `pressure_trend` has been called.
+    pressure_trend: Option<PressureTrend>,
+    /// Hard-iron calibration for the magnetometer.
+    compass_calibration: Option<CompassCalibration>,
+    /// Added to compass headings, in degrees, to turn them from magnetic
+    /// north to true north.
+    declination: f64,
+    /// Subtracted from accelerometer readings, in Gs.
+    accel_offset: Vector3D,
+    /// Multiplies accelerometer readings, after `accel_offset`.
+    accel_scale: Vector3D,
+    /// How far the LED matrix picture is turned.
+    rotation: Rotation,
+    /// The fonts used to render text on the LED matrix.
+    #[cfg(feature = "led-matrix")]
+    fonts: FontCollection,
+    /// The LED matrix framebuffer device, e.g. `/dev/fb1`.
+    #[cfg(feature = "led-matrix")]
+    framebuffer: String,
+    /// The LED matrix framebuffer, once it has been opened.
+    #[cfg(feature = "led-matrix")]
+    screen: Option<sensehat_screen::framebuffer::Framebuffer>,
+    /// The frame being drawn between `begin_frame` and `present`.
+    #[cfg(feature = "led-matrix")]
+    back_buffer: Option<[u8; 128]>,
+    /// Whether to blank the LED matrix when this is dropped.
+    #[cfg(feature = "led-matrix")]
+    clear_on_drop: bool,
+    /// The joystick input device, if it has been set rather than detected.
+    joystick: Option<String>,
+}
+
+#[cfg(feature = "led-matrix")]
+impl<'a, D> Drop for SenseHat<'a, D>
+where
+    D: I2CDevice,
+{
+    fn drop(&mut self) {
+        if self.clear_on_drop {
+            // Only if we've drawn anything, and there's nobody to tell if
+            // this fails
+            if let Some(ref mut screen) = self.screen {
+                screen.write_frame(&[0u8; 128]);
+            }
+        }
+    }
+}
+
+/// The part of the Sense HAT that a `SenseHatError::NotReady` is about.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Subsystem {
+    Pressure,
+    Temperature,
+    Humidity,
+    Imu,
+}
+
+impl Display for Subsystem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Subsystem::Pressure => write!(f, "pressure"),
+            Subsystem::Temperature => write!(f, "temperature"),
+            Subsystem::Humidity => write!(f, "humidity"),
+            Subsystem::Imu => write!(f, "IMU"),
+        }
+    }
 }
 
 /// Errors that this crate can return.
 #[derive(Debug)]
 pub enum SenseHatError {
-    NotReady,
+    /// The given sensor is powered down, or has no reading available yet.
+    NotReady(Subsystem),
+    /// The given sensor gave no fresh reading within the given time.
+    Timeout(Subsystem, ::std::time::Duration),
     GenericError,
     I2CError(LinuxI2CError),
     LSM9DS1Error(lsm9ds1::Error),
     #[cfg(feature = "led-matrix")]
     ScreenError(sensehat_screen::error::ScreenError),
     CharacterError(std::string::FromUtf16Error),
+    PixelOutOfRange,
+    /// The number was too big to show on the LED matrix.
+    NumberOutOfRange(u8),
+    /// The named chip was not found, and the given byte was read from its
+    /// WHO_AM_I register instead.
+    WrongChip(&'static str, u8),
+    /// The named chip was found instead of one of the usual ones, which
+    /// means this is a Sense HAT v2. These chips aren't supported yet.
+    UnsupportedChip(&'static str),
+    /// The LED matrix framebuffer device at the given path couldn't be
+    /// opened. The `io::Error` says why, e.g. `NotFound` or
+    /// `PermissionDenied`.
+    #[cfg(feature = "led-matrix")]
+    FramebufferError(String, ::std::io::Error),
+    /// The framebuffer device at the given path isn't the LED matrix's
+    /// format, so is probably the wrong one (e.g. the HDMI output).
+    #[cfg(feature = "led-matrix")]
+    WrongFramebufferFormat(String, FramebufferFormat),
+    /// The joystick input device couldn't be found or opened.
+    JoystickError(::std::io::Error),
+    /// The joystick input device went away while it was being read, for
+    /// example because the Sense HAT was unplugged. Call `joystick()` (or
+    /// `SenseHat::detect_joystick_device`) again to find it once it's back.
+    JoystickDisconnected,
+    /// An image of the given width and height had the given number of bytes
+    /// of data, rather than three (red, green, blue) per pixel.
+    #[cfg(feature = "led-matrix")]
+    WrongImageSize(u32, u32, usize),
+    /// Writing to something other than a Sense HAT device failed, e.g. the
+    /// writer given to `log_csv`.
+    Io(::std::io::Error),
 }
 
 impl Display for SenseHatError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            SenseHatError::NotReady => write!(f, "Not ready error"),
+            SenseHatError::NotReady(subsystem) => {
+                write!(f, "No {} reading available", subsystem)
+            }
+            SenseHatError::Timeout(subsystem, timeout) => write!(
+                f,
+                "No new {} reading within {:?}; is the sensor stuck?",
+                subsystem, timeout
+            ),
             SenseHatError::GenericError => write!(f, "Generic error"),
             SenseHatError::I2CError(err) => write!(f, "I2C error: {}", err),
             SenseHatError::LSM9DS1Error(err) => write!(f, "LSM9DS1 error: {}", err),
             #[cfg(feature = "led-matrix")]
-            SenseHatError::ScreenError(err) => write!(f, "Screen error: {}", err),
+            SenseHatError::ScreenError(sensehat_screen::error::ScreenError::Framebuffer(err)) => {
+                // FramebufferError's own Display impl recurses forever, so
+                // use the details directly.
+                write!(f, "Screen error: {}", err.details)
+            }
+            #[cfg(feature = "led-matrix")]
+            SenseHatError::ScreenError(sensehat_screen::error::ScreenError::Unicode(err)) => {
+                write!(f, "Screen error: {}", err)
+            }
             SenseHatError::CharacterError(err) => write!(f, "Character error: {}", err),
+            SenseHatError::PixelOutOfRange => write!(f, "Pixel out of range"),
+            SenseHatError::NumberOutOfRange(value) => {
+                write!(f, "Number {} out of range (0 to 99)", value)
+            }
+            SenseHatError::WrongChip(chip, found) => write!(
+                f,
+                "{} not found (WHO_AM_I was 0x{:02x}), is this a Sense HAT on the right I2C bus?",
+                chip, found
+            ),
+            SenseHatError::UnsupportedChip(chip) => write!(
+                f,
+                "Found a {}, so this is a Sense HAT v2, which isn't supported yet",
+                chip
+            ),
+            #[cfg(feature = "led-matrix")]
+            SenseHatError::FramebufferError(path, err) => {
+                write!(f, "Couldn't open framebuffer {}: {}", path, err)
+            }
+            #[cfg(feature = "led-matrix")]
+            SenseHatError::WrongFramebufferFormat(path, format) => write!(
+                f,
+                "Framebuffer {} is {}x{} at {} bits per pixel, not the LED matrix's 8x8 at 16; is it the HDMI output?",
+                path, format.width, format.height, format.bits_per_pixel
+            ),
+            SenseHatError::JoystickError(err) => write!(f, "Joystick error: {}", err),
+            SenseHatError::JoystickDisconnected => write!(f, "The joystick has been disconnected"),
+            #[cfg(feature = "led-matrix")]
+            SenseHatError::WrongImageSize(width, height, len) => write!(
+                f,
+                "A {}x{} RGB image should have {} bytes, not {}",
+                width,
+                height,
+                u64::from(*width) * u64::from(*height) * 3,
+                len
+            ),
+            SenseHatError::Io(err) => write!(f, "I/O error: {}", err),
+        }
+    }
+}
+
+impl Error for SenseHatError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SenseHatError::I2CError(err) => Some(err),
+            SenseHatError::LSM9DS1Error(err) => Some(err),
+            SenseHatError::CharacterError(err) => Some(err),
+            #[cfg(feature = "led-matrix")]
+            SenseHatError::FramebufferError(_, err) => Some(err),
+            SenseHatError::JoystickError(err) => Some(err),
+            SenseHatError::Io(err) => Some(err),
+            _ => None,
         }
     }
 }
 
-impl Error for SenseHatError {}
+/// Standard gravity, in m/s².
+const STANDARD_GRAVITY: f64 = 9.80665;
 
 /// A shortcut for Results that can return `T` or `SenseHatError`.
 pub type SenseHatResult<T> = Result<T, SenseHatError>;
@@ -160,135 +729,1445 @@ impl<'a> SenseHat<'a> {
     /// Will open the relevant I2C devices and then attempt to initialise the
     /// chips on the Sense HAT.
     pub fn new() -> SenseHatResult<SenseHat<'a>> {
+        SenseHat::with_i2c_bus("/dev/i2c-1")
+    }
+
+    /// Finds the joystick's input device, e.g. `/dev/input/event2`, by
+    /// looking in `/sys/class/input` for the one the kernel calls "Raspberry
+    /// Pi Sense HAT Joystick". This is what `joystick` opens unless you've
+    /// called `set_joystick_path`. Returns a `JoystickError` with
+    /// `ErrorKind::NotFound` if there isn't one, which usually means the
+    /// `rpisense-js` driver isn't loaded.
+    pub fn detect_joystick_device() -> SenseHatResult<::std::path::PathBuf> {
+        joystick::detect_joystick(::std::path::Path::new("/sys/class/input"))
+            .map(::std::path::PathBuf::from)
+            .ok_or_else(|| {
+                SenseHatError::JoystickError(::std::io::Error::new(
+                    ::std::io::ErrorKind::NotFound,
+                    "no Sense HAT joystick input device",
+                ))
+            })
+    }
+
+    /// Returns a `SenseHatBuilder`, for creating a SenseHat object with
+    /// several non-default settings.
+    pub fn builder() -> SenseHatBuilder {
+        SenseHatBuilder::new()
+    }
+
+    /// Try and create a new SenseHat object, using the sensors on the given
+    /// I2C bus (e.g. `/dev/i2c-0`) instead of the default `/dev/i2c-1`.
+    ///
+    /// With `RTIMULib`, the IMU is still found using the default settings
+    /// file; see "More than one Sense HAT" in the crate documentation.
+    pub fn with_i2c_bus(path: &str) -> SenseHatResult<SenseHat<'a>> {
+        SenseHat::with_i2c_addresses(path, hts221::I2C_ADDRESS, lps25h::I2C_ADDRESS)
+    }
+
+    /// Try and create a new SenseHat object, using the given I2C bus and the
+    /// given addresses for the HTS221 humidity sensor (normally 0x5f) and
+    /// the LPS25H pressure sensor (normally 0x5c).
+    pub fn with_i2c_addresses(
+        path: &str,
+        hts221_addr: u16,
+        lps25h_addr: u16,
+    ) -> SenseHatResult<SenseHat<'a>> {
+        SenseHat::open(
+            path,
+            hts221_addr,
+            lps25h_addr,
+            None,
+            i2c_timeout::DEFAULT_TIMEOUT,
+        )
+    }
+
+    /// Try and create a new SenseHat object, loading the IMU settings (and
+    /// hence the magnetometer and accelerometer calibration) from the given
+    /// `RTIMULib` settings file, e.g.
+    /// `/home/pi/.config/sense_hat/RTIMULib.ini`.
+    pub fn with_imu_settings<P>(settings: P) -> SenseHatResult<SenseHat<'a>>
+    where
+        P: AsRef<::std::path::Path>,
+    {
+        SenseHat::open(
+            "/dev/i2c-1",
+            hts221::I2C_ADDRESS,
+            lps25h::I2C_ADDRESS,
+            Some(settings.as_ref()),
+            i2c_timeout::DEFAULT_TIMEOUT,
+        )
+    }
+
+    /// Starts a thread which samples every sensor once per `interval`, and
+    /// returns a channel of the readings. See
+    /// `SenseHatBuilder::spawn_sampler`.
+    pub fn spawn_sampler(
+        interval: ::std::time::Duration,
+    ) -> ::std::sync::mpsc::Receiver<SenseHatResult<Reading>> {
+        SenseHatBuilder::new().spawn_sampler(interval)
+    }
+
+    fn open(
+        path: &str,
+        hts221_addr: u16,
+        lps25h_addr: u16,
+        imu_settings: Option<&::std::path::Path>,
+        i2c_timeout: ::std::time::Duration,
+    ) -> SenseHatResult<SenseHat<'a>> {
+        let hts221_dev = LinuxI2CDevice::new(path, hts221_addr)?;
+        let lps25h_dev = LinuxI2CDevice::new(path, lps25h_addr)?;
+        // This covers the whole bus, so once is enough
+        i2c_timeout::set_timeout(&hts221_dev, i2c_timeout)?;
+        let imu = open_imu(path, imu_settings)?;
+        SenseHat::from_devices(hts221_dev, lps25h_dev, imu).map_err(|err| match err {
+            // The Sense HAT v2 has an SHTC3 instead of the HTS221
+            SenseHatError::I2CError(_) if probe_shtc3(path) => {
+                SenseHatError::UnsupportedChip("SHTC3")
+            }
+            err => err,
+        })
+    }
+}
+
+impl<'a> SenseHat<'a, SharedI2CDevice> {
+    /// Try and create a new SenseHat object, like `with_i2c_bus`, but with
+    /// the humidity and pressure sensors sharing a single file descriptor
+    /// for the I2C bus. The IMU still opens the bus for itself.
+    pub fn with_shared_bus(path: &str) -> SenseHatResult<SenseHat<'a, SharedI2CDevice>> {
+        let bus = SharedI2CBus::open(path)?;
+        let imu = open_imu(path, None)?;
+        SenseHat::from_devices(
+            bus.device(hts221::I2C_ADDRESS),
+            bus.device(lps25h::I2C_ADDRESS),
+            imu,
+        )
+    }
+}
+
+impl<'a, D> SenseHat<'a, D>
+where
+    D: I2CDevice,
+    SenseHatError: From<D::Error>,
+{
+    /// Try and create a new SenseHat object which talks to the HTS221
+    /// humidity sensor and the LPS25H pressure sensor through the given I2C
+    /// devices. This lets you run against a `mock::MockI2CDevice` on a
+    /// machine without a Sense HAT. The IMU is opened with `RTIMULib` as
+    /// normal if the `rtimu` feature is enabled, and is unavailable if not.
+    pub fn with_devices(hts221_dev: D, lps25h_dev: D) -> SenseHatResult<SenseHat<'a, D>> {
+        let imu = Box::new(lsm9ds1::Lsm9ds1::new(None)?);
+        SenseHat::from_devices(hts221_dev, lps25h_dev, imu)
+    }
+
+    fn from_devices(
+        mut hts221_dev: D,
+        mut lps25h_dev: D,
+        imu: Box<dyn ImuBackend + 'a>,
+    ) -> SenseHatResult<SenseHat<'a, D>> {
+        check_who_am_i(
+            &mut hts221_dev,
+            "HTS221",
+            hts221::REG_WHO_AM_I,
+            hts221::WHO_AM_I,
+        )?;
+        check_who_am_i(
+            &mut lps25h_dev,
+            "LPS25H",
+            lps25h::REG_WHO_AM_I,
+            lps25h::WHO_AM_I,
+        )?;
         Ok(SenseHat {
-            humidity_chip: hts221::Hts221::new(LinuxI2CDevice::new("/dev/i2c-1", 0x5f)?)?,
-            pressure_chip: lps25h::Lps25h::new(LinuxI2CDevice::new("/dev/i2c-1", 0x5c)?)?,
-            accelerometer_chip: lsm9ds1::Lsm9ds1::new()?,
+            humidity_chip: hts221::Hts221::new(hts221_dev)?,
+            pressure_chip: lps25h::Lps25h::new(lps25h_dev)?,
+            accelerometer_chip: imu,
             data: ImuData::default(),
+            imu_mode: None,
+            temperature_offset: 0.0,
+            retry: RetryPolicy::default(),
+            pressure_trend: None,
+            compass_calibration: None,
+            declination: 0.0,
+            accel_offset: Vector3D {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            accel_scale: Vector3D {
+                x: 1.0,
+                y: 1.0,
+                z: 1.0,
+            },
+            rotation: Rotation::Deg0,
+            #[cfg(feature = "led-matrix")]
+            fonts: FontCollection::new(),
+            #[cfg(feature = "led-matrix")]
+            framebuffer: detect_framebuffer(::std::path::Path::new("/sys/class/graphics"))
+                .unwrap_or_else(|| String::from("/dev/fb1")),
+            #[cfg(feature = "led-matrix")]
+            screen: None,
+            #[cfg(feature = "led-matrix")]
+            back_buffer: None,
+            #[cfg(feature = "led-matrix")]
+            clear_on_drop: false,
+            joystick: None,
         })
     }
 
-    /// Returns a Temperature reading from the barometer.  It's less accurate
-    /// than the barometer (+/- 2 degrees C), but over a wider range.
-    pub fn get_temperature_from_pressure(&mut self) -> SenseHatResult<Temperature> {
-        let status = self.pressure_chip.status()?;
-        if (status & 1) != 0 {
-            Ok(Temperature::from_celsius(
-                self.pressure_chip.get_temp_celcius()?,
-            ))
-        } else {
-            Err(SenseHatError::NotReady)
-        }
+    /// Reboots the humidity and pressure sensors and re-runs their init
+    /// sequences, reloading the humidity sensor's calibration. Use this to
+    /// recover sensors which have stopped returning fresh data (e.g. after a
+    /// brown-out). Any averaging set with `set_humidity_averaging` is lost.
+    pub fn reset(&mut self) -> SenseHatResult<()> {
+        self.humidity_chip.reset()?;
+        self.pressure_chip.reset()?;
+        Ok(())
     }
 
-    /// Returns a Pressure value from the barometer
-    pub fn get_pressure(&mut self) -> SenseHatResult<Pressure> {
-        let status = self.pressure_chip.status()?;
-        if (status & 2) != 0 {
-            Ok(Pressure::from_hectopascals(
-                self.pressure_chip.get_pressure_hpa()?,
-            ))
-        } else {
-            Err(SenseHatError::NotReady)
-        }
+    /// Powers down the humidity and pressure sensors, so they draw almost no
+    /// current. Until `power_up` is called, reading from them returns
+    /// `SenseHatError::NotReady`. Does nothing if they are already powered
+    /// down.
+    pub fn power_down(&mut self) -> SenseHatResult<()> {
+        self.humidity_chip.power_down()?;
+        self.pressure_chip.power_down()?;
+        Ok(())
     }
 
-    /// Returns a Temperature reading from the humidity sensor. It's more
-    /// accurate than the barometer (+/- 0.5 degrees C), but over a smaller
-    /// range.
-    pub fn get_temperature_from_humidity(&mut self) -> SenseHatResult<Temperature> {
-        let status = self.humidity_chip.status()?;
-        if (status & 1) != 0 {
-            let celcius = self.humidity_chip.get_temperature_celcius()?;
-            Ok(Temperature::from_celsius(celcius))
-        } else {
-            Err(SenseHatError::NotReady)
-        }
+    /// Powers the humidity and pressure sensors back up after `power_down`.
+    /// Does nothing if they are already powered up.
+    pub fn power_up(&mut self) -> SenseHatResult<()> {
+        self.humidity_chip.power_up()?;
+        self.pressure_chip.power_up()?;
+        Ok(())
     }
 
-    /// Returns a RelativeHumidity value in percent between 0 and 100
-    pub fn get_humidity(&mut self) -> SenseHatResult<RelativeHumidity> {
-        let status = self.humidity_chip.status()?;
-        if (status & 2) != 0 {
-            let percent = self.humidity_chip.get_relative_humidity_percent()?;
-            Ok(RelativeHumidity::from_percent(percent))
-        } else {
-            Err(SenseHatError::NotReady)
-        }
+    /// Sets an offset which is subtracted from every temperature reading, to
+    /// correct for the Sense HAT being warmed by the Raspberry Pi underneath
+    /// it. For example, if the Sense HAT reads 3 degrees C too high, pass
+    /// `Temperature::from_celsius(3.0)`. The default is zero.
+    ///
+    /// The `_uncorrected` temperature methods ignore this offset.
+    pub fn set_temperature_offset(&mut self, offset: Temperature) {
+        self.temperature_offset = offset.as_celsius();
     }
 
-    /// Returns a vector representing the current orientation, using all
-    /// three sensors.
-    pub fn get_orientation(&mut self) -> SenseHatResult<Orientation> {
-        self.accelerometer_chip.set_fusion();
-        if self.accelerometer_chip.imu_read() {
-            self.data = self.accelerometer_chip.get_imu_data()?;
-        }
-        match self.data.fusion_pose {
-            Some(o) => Ok(o),
-            None => Err(SenseHatError::NotReady),
-        }
+    /// Sets how the temperature, pressure and humidity getters retry reads
+    /// which fail with a transient I2C error, such as `EREMOTEIO` from a
+    /// noisy bus. Other errors, like `ENODEV`, are returned straight away.
+    /// The default is `RetryPolicy::default()`; use `RetryPolicy::none()` to
+    /// turn retries off.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry = policy;
     }
 
-    /// Get the compass heading (ignoring gyro and magnetometer)
-    pub fn get_compass(&mut self) -> SenseHatResult<Angle> {
-        self.accelerometer_chip.set_compass_only();
-        if self.accelerometer_chip.imu_read() {
-            // Don't cache this data
-            let data = self.accelerometer_chip.get_imu_data()?;
-            match data.fusion_pose {
-                Some(o) => Ok(o.yaw),
-                None => Err(SenseHatError::NotReady),
-            }
-        } else {
-            Err(SenseHatError::NotReady)
+    /// Returns a Temperature reading from the best available sensor: the
+    /// humidity sensor, which is more accurate, or the barometer if the
+    /// humidity sensor has no reading ready.
+    ///
+    /// The offset from `set_temperature_offset` is applied.
+    pub fn get_temperature(&mut self) -> SenseHatResult<Temperature> {
+        match self.get_temperature_from_humidity() {
+            Err(SenseHatError::NotReady(_)) => self.get_temperature_from_pressure(),
+            result => result,
         }
     }
 
-    /// Returns a vector representing the current orientation using only
-    /// the gyroscope.
-    pub fn get_gyro(&mut self) -> SenseHatResult<Orientation> {
-        self.accelerometer_chip.set_gyro_only();
-        if self.accelerometer_chip.imu_read() {
-            let data = self.accelerometer_chip.get_imu_data()?;
-            match data.fusion_pose {
-                Some(o) => Ok(o),
-                None => Err(SenseHatError::NotReady),
-            }
-        } else {
-            Err(SenseHatError::NotReady)
-        }
+    /// Returns the temperature from `get_temperature`, in degrees
+    /// Fahrenheit.
+    pub fn get_temperature_fahrenheit(&mut self) -> SenseHatResult<f64> {
+        self.get_temperature().map(|t| t.as_fahrenheit())
     }
 
-    /// Returns a vector representing the current orientation using only
-    /// the accelerometer.
-    pub fn get_accel(&mut self) -> SenseHatResult<Orientation> {
-        self.accelerometer_chip.set_accel_only();
-        if self.accelerometer_chip.imu_read() {
-            let data = self.accelerometer_chip.get_imu_data()?;
-            match data.fusion_pose {
-                Some(o) => Ok(o),
-                None => Err(SenseHatError::NotReady),
-            }
-        } else {
-            Err(SenseHatError::NotReady)
-        }
+    /// Returns the temperature from `get_temperature`, in kelvin.
+    pub fn get_temperature_kelvin(&mut self) -> SenseHatResult<f64> {
+        self.get_temperature().map(|t| t.as_kelvin())
     }
 
-    /// Returns a vector representing the current acceleration in Gs.
-    pub fn get_accel_raw(&mut self) -> SenseHatResult<Vector3D> {
-        self.accelerometer_chip.set_accel_only();
-        if self.accelerometer_chip.imu_read() {
-            self.data = self.accelerometer_chip.get_imu_data()?;
-        }
-        match self.data.accel {
-            Some(a) => Ok(a),
-            None => Err(SenseHatError::NotReady),
+    /// Returns the temperature from `get_temperature_from_humidity`, in
+    /// degrees Fahrenheit.
+    pub fn get_temperature_from_humidity_fahrenheit(&mut self) -> SenseHatResult<f64> {
+        self.get_temperature_from_humidity()
+            .map(|t| t.as_fahrenheit())
+    }
+
+    /// Returns the temperature from `get_temperature_from_humidity`, in
+    /// kelvin.
+    pub fn get_temperature_from_humidity_kelvin(&mut self) -> SenseHatResult<f64> {
+        self.get_temperature_from_humidity().map(|t| t.as_kelvin())
+    }
+
+    /// Returns the temperature from `get_temperature_from_pressure`, in
+    /// degrees Fahrenheit.
+    pub fn get_temperature_from_pressure_fahrenheit(&mut self) -> SenseHatResult<f64> {
+        self.get_temperature_from_pressure()
+            .map(|t| t.as_fahrenheit())
+    }
+
+    /// Returns the temperature from `get_temperature_from_pressure`, in
+    /// kelvin.
+    pub fn get_temperature_from_pressure_kelvin(&mut self) -> SenseHatResult<f64> {
+        self.get_temperature_from_pressure().map(|t| t.as_kelvin())
+    }
+
+    /// Returns the mean of the humidity sensor and barometer temperatures,
+    /// which is steadier than either on its own. Each is weighted by its
+    /// stated accuracy (+/- 0.5 and +/- 2 degrees C), so the humidity
+    /// sensor counts for 16 times as much. If only one sensor has a reading
+    /// ready, that reading is returned.
+    ///
+    /// The offset from `set_temperature_offset` is applied.
+    pub fn get_temperature_averaged(&mut self) -> SenseHatResult<Temperature> {
+        let humidity = if_ready(self.get_temperature_from_humidity())?;
+        let pressure = if_ready(self.get_temperature_from_pressure())?;
+        match (humidity, pressure) {
+            (Some(h), Some(p)) => {
+                // Inverse-variance weights
+                let h_weight = 1.0 / (0.5 * 0.5);
+                let p_weight = 1.0 / (2.0 * 2.0);
+                Ok(Temperature::from_celsius(
+                    (h.as_celsius() * h_weight + p.as_celsius() * p_weight) / (h_weight + p_weight),
+                ))
+            }
+            (Some(t), None) | (None, Some(t)) => Ok(t),
+            (None, None) => Err(SenseHatError::NotReady(Subsystem::Temperature)),
         }
     }
 
-    /// Displays a scrolling message on the LED matrix. Blocks until the
-    /// entire message has scrolled past.
+    /// Returns a Temperature reading from the barometer.  It's less accurate
+    /// than the barometer (+/- 2 degrees C), but over a wider range.
+    ///
+    /// The offset from `set_temperature_offset` is applied.
+    pub fn get_temperature_from_pressure(&mut self) -> SenseHatResult<Temperature> {
+        let temp = self.get_temperature_from_pressure_uncorrected()?;
+        Ok(self.apply_temperature_offset(temp))
+    }
+
+    /// Returns a Temperature reading from the barometer, ignoring any offset
+    /// from `set_temperature_offset`.
+    pub fn get_temperature_from_pressure_uncorrected(&mut self) -> SenseHatResult<Temperature> {
+        retry::retry(self.retry, || {
+            if !self.pressure_chip.is_powered() {
+                return Err(SenseHatError::NotReady(Subsystem::Temperature));
+            }
+            let status = self.pressure_chip.status()?;
+            if (status & 1) != 0 {
+                Ok(Temperature::from_celsius(
+                    self.pressure_chip.get_temp_celcius()?,
+                ))
+            } else {
+                Err(SenseHatError::NotReady(Subsystem::Temperature))
+            }
+        })
+    }
+
+    /// Returns a Pressure value from the barometer
+    pub fn get_pressure(&mut self) -> SenseHatResult<Pressure> {
+        retry::retry(self.retry, || {
+            if !self.pressure_chip.is_powered() {
+                return Err(SenseHatError::NotReady(Subsystem::Pressure));
+            }
+            let status = self.pressure_chip.status()?;
+            if (status & 2) != 0 {
+                Ok(Pressure::from_hectopascals(
+                    self.pressure_chip.get_pressure_hpa()?,
+                ))
+            } else {
+                Err(SenseHatError::NotReady(Subsystem::Pressure))
+            }
+        })
+    }
+
+    /// Returns the pressure from `get_pressure`, in pascals.
+    pub fn get_pressure_pa(&mut self) -> SenseHatResult<f64> {
+        self.get_pressure().map(|p| p.as_pascals())
+    }
+
+    /// Returns the pressure from `get_pressure`, in millibars (which are the
+    /// same as hectopascals).
+    pub fn get_pressure_mbar(&mut self) -> SenseHatResult<f64> {
+        self.get_pressure().map(|p| p.as_millibars())
+    }
+
+    /// Reads the barometer and adds the reading to the readings kept from
+    /// earlier calls, dropping any older than `window`, then returns them
+    /// so you can see which way the pressure is heading. Call this every few
+    /// minutes, with the same `window` (three hours is usual for weather
+    /// forecasting). If no new reading is ready, the trend so far is
+    /// returned.
+    pub fn pressure_trend(
+        &mut self,
+        window: ::std::time::Duration,
+    ) -> SenseHatResult<&PressureTrend> {
+        let reading = if_ready(self.get_pressure_timestamped())?;
+        let trend = self
+            .pressure_trend
+            .get_or_insert_with(|| PressureTrend::new(window));
+        trend.set_window(window);
+        if let Some((pressure, time)) = reading {
+            trend.add(time, pressure);
+        }
+        Ok(trend)
+    }
+
+    /// Returns a Pressure value from the barometer, having first triggered a
+    /// single conversion and waited (up to half a second) for it to complete.
+    ///
+    /// This takes the barometer out of continuous conversion mode, so it
+    /// draws very little power between calls. Once you have called this,
+    /// `get_pressure` and `get_temperature_from_pressure` will only return
+    /// the values from the most recent one-shot measurement.
+    pub fn get_pressure_oneshot(&mut self) -> SenseHatResult<Pressure> {
+        retry::retry(self.retry, || {
+            if !self.pressure_chip.is_powered() {
+                return Err(SenseHatError::NotReady(Subsystem::Pressure));
+            }
+            self.pressure_chip.trigger_one_shot()?;
+            for _ in 0..100 {
+                if (self.pressure_chip.status()? & 2) != 0 {
+                    return Ok(Pressure::from_hectopascals(
+                        self.pressure_chip.get_pressure_hpa()?,
+                    ));
+                }
+                ::std::thread::sleep(::std::time::Duration::from_millis(5));
+            }
+            Err(SenseHatError::NotReady(Subsystem::Pressure))
+        })
+    }
+
+    /// Returns an estimate of the altitude, calculated from the barometer
+    /// using the international barometric formula. `sea_level_hpa` is the
+    /// current pressure at sea level, in hPa, for your location.
+    pub fn get_altitude(&mut self, sea_level_hpa: f64) -> SenseHatResult<Length> {
+        let pressure = self.get_pressure()?;
+        Ok(Length::from_meters(weather::altitude_metres(
+            pressure.as_hectopascals(),
+            sea_level_hpa,
+        )))
+    }
+
+    /// Returns an estimate of the altitude, assuming the standard sea-level
+    /// pressure of 1013.25 hPa.
+    pub fn get_altitude_standard(&mut self) -> SenseHatResult<Length> {
+        self.get_altitude(weather::STANDARD_SEA_LEVEL_HPA)
+    }
+
+    /// Returns a Temperature reading from the humidity sensor. It's more
+    /// accurate than the barometer (+/- 0.5 degrees C), but over a smaller
+    /// range.
+    ///
+    /// The offset from `set_temperature_offset` is applied.
+    pub fn get_temperature_from_humidity(&mut self) -> SenseHatResult<Temperature> {
+        let temp = self.get_temperature_from_humidity_uncorrected()?;
+        Ok(self.apply_temperature_offset(temp))
+    }
+
+    /// Returns a Temperature reading from the humidity sensor, ignoring any
+    /// offset from `set_temperature_offset`.
+    pub fn get_temperature_from_humidity_uncorrected(&mut self) -> SenseHatResult<Temperature> {
+        retry::retry(self.retry, || {
+            if !self.humidity_chip.is_powered() {
+                return Err(SenseHatError::NotReady(Subsystem::Temperature));
+            }
+            let status = self.humidity_chip.status()?;
+            if (status & 1) != 0 {
+                let celcius = self.humidity_chip.get_temperature_celcius()?;
+                Ok(Temperature::from_celsius(celcius))
+            } else {
+                Err(SenseHatError::NotReady(Subsystem::Temperature))
+            }
+        })
+    }
+
+    /// Sets how many samples the humidity sensor averages for each humidity
+    /// and temperature reading. More averaging reduces jitter in the
+    /// readings, at the cost of power. The default is
+    /// `HumidityAveraging::Samples32` and `TemperatureAveraging::Samples16`.
+    pub fn set_humidity_averaging(
+        &mut self,
+        humidity: HumidityAveraging,
+        temperature: TemperatureAveraging,
+    ) -> SenseHatResult<()> {
+        self.humidity_chip.set_averaging(humidity, temperature)?;
+        Ok(())
+    }
+
+    /// Sets how many samples the pressure sensor averages for each pressure
+    /// reading. More averaging smooths out the noise, but makes the reading
+    /// slower to follow changes; see `PressureAveraging` for the lag of
+    /// each. This only affects `get_pressure`, not `get_pressure_oneshot`.
+    pub fn set_pressure_averaging(&mut self, averaging: PressureAveraging) -> SenseHatResult<()> {
+        self.pressure_chip.set_averaging(averaging)?;
+        Ok(())
+    }
+
+    /// Returns the pressure sensor's reference pressure (its REF_P
+    /// registers). This is zero unless it has been set with
+    /// `set_pressure_reference` or `set_pressure_auto_zero`.
+    ///
+    /// This, and the other reference pressure methods, are for advanced
+    /// calibration; most programs won't need them.
+    pub fn get_pressure_reference(&mut self) -> SenseHatResult<Pressure> {
+        let reference = self.pressure_chip.get_reference_pressure()?;
+        Ok(Pressure::from_hectopascals(f64::from(reference) / 4096.0))
+    }
+
+    /// Writes the pressure sensor's reference pressure, with a resolution
+    /// of 1/4096 hPa. According to the LPS25H datasheet, the chip only
+    /// subtracts it from its readings while auto-zero is on (see
+    /// `set_pressure_auto_zero`); otherwise it is only used for the chip's
+    /// pressure threshold interrupt, which this crate doesn't use. It is
+    /// mostly useful for putting back a reference saved from
+    /// `get_pressure_reference` after auto-zero has been turned on.
+    pub fn set_pressure_reference(&mut self, reference: Pressure) -> SenseHatResult<()> {
+        let reference = (reference.as_hectopascals() * 4096.0).round() as i32;
+        self.pressure_chip.set_reference_pressure(reference)?;
+        Ok(())
+    }
+
+    /// Turns the pressure sensor's auto-zero on or off. Turning it on makes
+    /// the chip store the current pressure as its reference, and from then
+    /// on `get_pressure` returns the difference from that, which can be
+    /// negative, rather than the absolute pressure. That lets a station
+    /// zero its barometer at a known reference. Turning it off clears the
+    /// reference, so readings are absolute again.
+    pub fn set_pressure_auto_zero(&mut self, enable: bool) -> SenseHatResult<()> {
+        self.pressure_chip.set_auto_zero(enable)?;
+        Ok(())
+    }
+
+    /// Sets how often the pressure sensor takes a reading. The default is
+    /// `PressureDataRate::TwentyFiveHz`; slower rates save power. This also
+    /// puts the sensor back into continuous mode after
+    /// `get_pressure_oneshot`. The first readings afterwards can be off;
+    /// `prime` skips them.
+    pub fn set_pressure_data_rate(&mut self, rate: PressureDataRate) -> SenseHatResult<()> {
+        self.pressure_chip.set_data_rate(rate)?;
+        Ok(())
+    }
+
+    /// Sets how often the humidity sensor takes a reading. The default is
+    /// `HumidityDataRate::TwelveHalfHz`; slower rates save power. The first
+    /// readings afterwards can be off; `prime` skips them.
+    pub fn set_humidity_data_rate(&mut self, rate: HumidityDataRate) -> SenseHatResult<()> {
+        self.humidity_chip.set_data_rate(rate)?;
+        Ok(())
+    }
+
+    /// Returns the humidity sensor's factory calibration, which turns its
+    /// raw readings into degrees C and percent. This is handy for checking
+    /// a sensor whose humidity readings look wrong.
+    pub fn get_humidity_calibration(&self) -> HumidityCalibration {
+        self.humidity_chip.calibration()
+    }
+
+    fn apply_temperature_offset(&self, temp: Temperature) -> Temperature {
+        Temperature::from_celsius(temp.as_celsius() - self.temperature_offset)
+    }
+
+    /// Returns a RelativeHumidity value in percent between 0 and 100
+    ///
+    /// Near the extremes the sensor's calibration can give values a little
+    /// outside that range, which are clamped. Use `get_humidity_unclamped`
+    /// to see them.
+    pub fn get_humidity(&mut self) -> SenseHatResult<RelativeHumidity> {
+        self.get_humidity_unclamped().map(|rh| rh.clamped())
+    }
+
+    /// Returns a RelativeHumidity value in percent, as calculated from the
+    /// sensor's calibration. It may be slightly below 0 or above 100; check
+    /// with `RelativeHumidity::is_in_range`.
+    pub fn get_humidity_unclamped(&mut self) -> SenseHatResult<RelativeHumidity> {
+        retry::retry(self.retry, || {
+            if !self.humidity_chip.is_powered() {
+                return Err(SenseHatError::NotReady(Subsystem::Humidity));
+            }
+            let status = self.humidity_chip.status()?;
+            if (status & 2) != 0 {
+                let percent = self.humidity_chip.get_relative_humidity_percent()?;
+                Ok(RelativeHumidity::from_percent(percent))
+            } else {
+                Err(SenseHatError::NotReady(Subsystem::Humidity))
+            }
+        })
+    }
+
+    /// Returns the temperature and relative humidity from the humidity
+    /// sensor, like `get_temperature_from_humidity` and `get_humidity`, but
+    /// checking the sensor's status once and reading both values together.
+    /// That is half the I2C traffic of calling both, and the pair always
+    /// comes from the same conversion. Returns `NotReady` unless both are
+    /// ready.
+    pub fn get_humidity_reading(&mut self) -> SenseHatResult<(Temperature, RelativeHumidity)> {
+        let (celsius, percent) = retry::retry(self.retry, || {
+            if !self.humidity_chip.is_powered() {
+                return Err(SenseHatError::NotReady(Subsystem::Humidity));
+            }
+            let status = self.humidity_chip.status()?;
+            if (status & 3) == 3 {
+                Ok(self.humidity_chip.get_temperature_and_humidity()?)
+            } else {
+                Err(SenseHatError::NotReady(Subsystem::Humidity))
+            }
+        })?;
+        Ok((
+            self.apply_temperature_offset(Temperature::from_celsius(celsius)),
+            RelativeHumidity::from_percent(percent).clamped(),
+        ))
+    }
+
+    /// Like `get_pressure`, but if no reading is ready yet, keeps polling
+    /// the sensor until one is or `timeout` has passed.
+    pub fn get_pressure_blocking(
+        &mut self,
+        timeout: ::std::time::Duration,
+    ) -> SenseHatResult<Pressure> {
+        let powered = self.pressure_chip.is_powered();
+        self.wait_for_reading(timeout, powered, SenseHat::get_pressure)
+    }
+
+    /// Like `get_temperature_from_pressure`, but if no reading is ready yet,
+    /// keeps polling the sensor until one is or `timeout` has passed.
+    pub fn get_temperature_from_pressure_blocking(
+        &mut self,
+        timeout: ::std::time::Duration,
+    ) -> SenseHatResult<Temperature> {
+        let powered = self.pressure_chip.is_powered();
+        self.wait_for_reading(timeout, powered, SenseHat::get_temperature_from_pressure)
+    }
+
+    /// Like `get_temperature_from_humidity`, but if no reading is ready yet,
+    /// keeps polling the sensor until one is or `timeout` has passed.
+    pub fn get_temperature_from_humidity_blocking(
+        &mut self,
+        timeout: ::std::time::Duration,
+    ) -> SenseHatResult<Temperature> {
+        let powered = self.humidity_chip.is_powered();
+        self.wait_for_reading(timeout, powered, SenseHat::get_temperature_from_humidity)
+    }
+
+    /// Like `get_humidity`, but if no reading is ready yet, keeps polling
+    /// the sensor until one is or `timeout` has passed.
+    pub fn get_humidity_blocking(
+        &mut self,
+        timeout: ::std::time::Duration,
+    ) -> SenseHatResult<RelativeHumidity> {
+        let powered = self.humidity_chip.is_powered();
+        self.wait_for_reading(timeout, powered, SenseHat::get_humidity)
+    }
+
+    /// Like `get_humidity`, but also returns the time the sensor was read,
+    /// on the monotonic clock. Use this to work out the real interval
+    /// between samples, however long it takes to store them.
+    ///
+    /// The time is taken once the reading has come back from the sensor, so
+    /// it doesn't include any time spent retrying (see `set_retry_policy`)
+    /// before the read that worked.
+    pub fn get_humidity_timestamped(
+        &mut self,
+    ) -> SenseHatResult<(RelativeHumidity, ::std::time::Instant)> {
+        self.get_humidity()
+            .map(|rh| (rh, ::std::time::Instant::now()))
+    }
+
+    /// Like `get_temperature`, but also returns the time the sensors were
+    /// read, on the monotonic clock, taken as `get_humidity_timestamped`
+    /// does.
+    pub fn get_temperature_timestamped(
+        &mut self,
+    ) -> SenseHatResult<(Temperature, ::std::time::Instant)> {
+        self.get_temperature()
+            .map(|t| (t, ::std::time::Instant::now()))
+    }
+
+    /// Like `get_pressure`, but also returns the time the sensor was read,
+    /// on the monotonic clock, taken as `get_humidity_timestamped` does.
+    pub fn get_pressure_timestamped(&mut self) -> SenseHatResult<(Pressure, ::std::time::Instant)> {
+        self.get_pressure()
+            .map(|p| (p, ::std::time::Instant::now()))
+    }
+
+    /// Waits for the next `samples` readings from the humidity and pressure
+    /// sensors and throws them away. The first readings after the sensors
+    /// are powered up, or after their data rate is changed, can be well out,
+    /// so calling this once at start-up means the first values you see are
+    /// good. A couple of samples is usually enough.
+    ///
+    /// Powered-down sensors are skipped. Each sample can take up to a
+    /// second at the slowest data rates; if one doesn't arrive within two
+    /// seconds, this returns `NotReady`.
+    pub fn prime(&mut self, samples: usize) -> SenseHatResult<()> {
+        let timeout = ::std::time::Duration::from_secs(2);
+        let humidity_powered = self.humidity_chip.is_powered();
+        let pressure_powered = self.pressure_chip.is_powered();
+        for _ in 0..samples {
+            if humidity_powered {
+                self.wait_for_reading(timeout, true, SenseHat::get_humidity_reading)?;
+            }
+            if pressure_powered {
+                self.wait_for_reading(timeout, true, SenseHat::get_pressure)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Calls `read` until it stops returning `NotReady` or `timeout` has
+    /// passed. A powered-down sensor will never be ready, so then `read` is
+    /// only called once.
+    fn wait_for_reading<T, F>(
+        &mut self,
+        timeout: ::std::time::Duration,
+        powered: bool,
+        mut read: F,
+    ) -> SenseHatResult<T>
+    where
+        F: FnMut(&mut Self) -> SenseHatResult<T>,
+    {
+        let start = ::std::time::Instant::now();
+        loop {
+            match read(self) {
+                Err(SenseHatError::NotReady(_)) if powered && start.elapsed() < timeout => {
+                    ::std::thread::sleep(::std::time::Duration::from_millis(2));
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Checks that the humidity and pressure sensors are working: that each
+    /// chip's WHO_AM_I register is right, and that each gives a reading
+    /// (within a second) which is physically plausible. I2C errors are
+    /// returned as errors, rather than in the report.
+    pub fn self_test(&mut self) -> SenseHatResult<SelfTestReport> {
+        Ok(SelfTestReport {
+            humidity: self.self_test_humidity()?,
+            pressure: self.self_test_pressure()?,
+        })
+    }
+
+    fn self_test_humidity(&mut self) -> SenseHatResult<SelfTestResult> {
+        let found = self.humidity_chip.who_am_i()?;
+        if found != hts221::WHO_AM_I {
+            return Ok(SelfTestResult::WrongChip(found));
+        }
+        let timeout = ::std::time::Duration::from_secs(1);
+        let powered = self.humidity_chip.is_powered();
+        let temp = if_ready(self.wait_for_reading(
+            timeout,
+            powered,
+            SenseHat::get_temperature_from_humidity_uncorrected,
+        ))?;
+        let rh =
+            if_ready(self.wait_for_reading(timeout, powered, SenseHat::get_humidity_unclamped))?;
+        Ok(match (temp, rh) {
+            (Some(temp), Some(rh)) => self_test::check_ranges(&[
+                (
+                    "temperature",
+                    temp.as_celsius(),
+                    self_test::TEMPERATURE_RANGE,
+                ),
+                ("humidity", rh.as_percent(), self_test::HUMIDITY_RANGE),
+            ]),
+            _ => SelfTestResult::NoReading,
+        })
+    }
+
+    fn self_test_pressure(&mut self) -> SenseHatResult<SelfTestResult> {
+        let found = self.pressure_chip.who_am_i()?;
+        if found != lps25h::WHO_AM_I {
+            return Ok(SelfTestResult::WrongChip(found));
+        }
+        let timeout = ::std::time::Duration::from_secs(1);
+        let powered = self.pressure_chip.is_powered();
+        let temp = if_ready(self.wait_for_reading(
+            timeout,
+            powered,
+            SenseHat::get_temperature_from_pressure_uncorrected,
+        ))?;
+        let pressure = if_ready(self.wait_for_reading(timeout, powered, SenseHat::get_pressure))?;
+        Ok(match (temp, pressure) {
+            (Some(temp), Some(pressure)) => self_test::check_ranges(&[
+                (
+                    "temperature",
+                    temp.as_celsius(),
+                    self_test::TEMPERATURE_RANGE,
+                ),
+                (
+                    "pressure",
+                    pressure.as_hectopascals(),
+                    self_test::PRESSURE_RANGE,
+                ),
+            ]),
+            _ => SelfTestResult::NoReading,
+        })
+    }
+
+    /// Returns the dew point, calculated from the humidity sensor's
+    /// temperature and relative humidity using the Magnus-Tetens
+    /// approximation.
+    ///
+    /// The approximation is accurate to within about 0.35 degrees C for
+    /// temperatures between -45 and 60 degrees C, and relative humidities
+    /// between 1% and 100%; drier readings are treated as 1%. The uncorrected
+    /// temperature is used (see `set_temperature_offset`), as the relative
+    /// humidity is measured at the sensor's own temperature.
+    pub fn get_dew_point(&mut self) -> SenseHatResult<Temperature> {
+        let temp = self.get_temperature_from_humidity_uncorrected()?;
+        let rh = self.get_humidity()?;
+        Ok(Temperature::from_celsius(weather::dew_point_celsius(
+            temp.as_celsius(),
+            rh.as_percent(),
+        )))
+    }
+
+    /// Returns the heat index (how hot it feels), calculated from the
+    /// humidity sensor's temperature and relative humidity using the Rothfusz
+    /// regression used by the US National Weather Service.
+    ///
+    /// The heat index isn't defined below about 27 degrees C, so below that
+    /// the temperature is returned unchanged. The offset from
+    /// `set_temperature_offset` is applied to the temperature first.
+    pub fn get_heat_index(&mut self) -> SenseHatResult<Temperature> {
+        let temp = self.get_temperature_from_humidity()?;
+        let rh = self.get_humidity()?;
+        Ok(Temperature::from_celsius(weather::heat_index_celsius(
+            temp.as_celsius(),
+            rh.as_percent(),
+        )))
+    }
+
+    /// Returns the absolute humidity, in grams of water per cubic metre of
+    /// air, calculated from the humidity sensor's temperature and relative
+    /// humidity.
+    ///
+    /// The saturation vapour pressure is found with the Magnus formula, and
+    /// the air is treated as an ideal gas, which is accurate to about 0.1%
+    /// between -30 and 35 degrees C. The uncorrected temperature is used (see
+    /// `set_temperature_offset`), as the relative humidity is measured at the
+    /// sensor's own temperature.
+    pub fn get_absolute_humidity(&mut self) -> SenseHatResult<f64> {
+        let temp = self.get_temperature_from_humidity_uncorrected()?;
+        let rh = self.get_humidity()?;
+        Ok(weather::absolute_humidity_g_m3(
+            temp.as_celsius(),
+            rh.as_percent(),
+        ))
+    }
+
+    /// Reads the temperature, pressure and humidity in one go. A sensor that
+    /// isn't ready gives `None` for its readings rather than failing the
+    /// whole read, but I2C errors are still returned.
+    ///
+    /// Each chip's status is checked once. When the humidity sensor has both
+    /// readings ready they are read together, as `get_humidity_reading`
+    /// does, so they come from the same conversion.
+    pub fn get_environment(&mut self) -> SenseHatResult<Environment> {
+        let (humidity_celsius, percent) = self.read_humidity_chip()?;
+        let (pressure_celsius, hpa) = self.read_pressure_chip()?;
+        let corrected =
+            |celsius: f64| self.apply_temperature_offset(Temperature::from_celsius(celsius));
+        Ok(Environment {
+            temperature_from_humidity: humidity_celsius.map(corrected),
+            temperature_from_pressure: pressure_celsius.map(corrected),
+            pressure: hpa.map(Pressure::from_hectopascals),
+            humidity: percent.map(|p| RelativeHumidity::from_percent(p).clamped()),
+        })
+    }
+
+    /// Reads whichever of the temperature (in degrees C) and relative
+    /// humidity (in percent) the humidity sensor has ready, checking its
+    /// status once.
+    fn read_humidity_chip(&mut self) -> SenseHatResult<(Option<f64>, Option<f64>)> {
+        retry::retry(self.retry, || {
+            if !self.humidity_chip.is_powered() {
+                return Ok((None, None));
+            }
+            let status = self.humidity_chip.status()?;
+            if (status & 3) == 3 {
+                let (celsius, percent) = self.humidity_chip.get_temperature_and_humidity()?;
+                return Ok((Some(celsius), Some(percent)));
+            }
+            let celsius = if (status & 1) != 0 {
+                Some(self.humidity_chip.get_temperature_celcius()?)
+            } else {
+                None
+            };
+            let percent = if (status & 2) != 0 {
+                Some(self.humidity_chip.get_relative_humidity_percent()?)
+            } else {
+                None
+            };
+            Ok((celsius, percent))
+        })
+    }
+
+    /// Reads whichever of the temperature (in degrees C) and pressure (in
+    /// hPa) the pressure sensor has ready, checking its status once.
+    fn read_pressure_chip(&mut self) -> SenseHatResult<(Option<f64>, Option<f64>)> {
+        retry::retry(self.retry, || {
+            if !self.pressure_chip.is_powered() {
+                return Ok((None, None));
+            }
+            let status = self.pressure_chip.status()?;
+            let celsius = if (status & 1) != 0 {
+                Some(self.pressure_chip.get_temp_celcius()?)
+            } else {
+                None
+            };
+            let hpa = if (status & 2) != 0 {
+                Some(self.pressure_chip.get_pressure_hpa()?)
+            } else {
+                None
+            };
+            Ok((celsius, hpa))
+        })
+    }
+
+    /// Writes `count` environmental readings to `writer` as CSV, one every
+    /// `interval`, after a header row. The time the reads take is allowed
+    /// for, so the rows don't drift. Each row has the time in seconds since
+    /// the Unix epoch, the temperature in degrees C, the pressure in hPa and
+    /// the relative humidity in percent. A sensor that isn't ready gives an
+    /// empty field. The writer is flushed every 10 rows. If writing fails,
+    /// the error is returned as `SenseHatError::Io`.
+    pub fn log_csv<W>(
+        &mut self,
+        mut writer: W,
+        interval: ::std::time::Duration,
+        count: usize,
+    ) -> SenseHatResult<()>
+    where
+        W: ::std::io::Write,
+    {
+        writeln!(writer, "timestamp,temperature,pressure,humidity")?;
+        let mut ticker = Ticker::new(interval);
+        for row in 0..count {
+            ticker.wait();
+            let timestamp = ::std::time::SystemTime::now()
+                .duration_since(::std::time::UNIX_EPOCH)
+                .unwrap_or_default();
+            let env = self.get_environment()?;
+            writeln!(
+                writer,
+                "{}.{:03},{},{},{}",
+                timestamp.as_secs(),
+                timestamp.subsec_millis(),
+                csv_field(env.temperature().map(|t| t.as_celsius())),
+                csv_field(env.pressure.map(|p| p.as_hectopascals())),
+                csv_field(env.humidity.map(|h| h.as_percent())),
+            )?;
+            if row % 10 == 9 {
+                writer.flush()?;
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Writes `count` orientation readings from `get_orientation` to
+    /// `writer` as CSV, one every `interval`, after a header row. Each row
+    /// has the time in seconds since the Unix epoch, then the roll, pitch
+    /// and yaw, and the compass heading from 0 to 360 (with the declination
+    /// from `set_declination`), all in degrees. If the IMU isn't ready, the
+    /// row has the time and empty fields. The writer is flushed every 10
+    /// rows. If writing fails, the error is returned as
+    /// `SenseHatError::Io`.
+    pub fn stream_orientation<W>(
+        &mut self,
+        mut writer: W,
+        interval: ::std::time::Duration,
+        count: usize,
+    ) -> SenseHatResult<()>
+    where
+        W: ::std::io::Write,
+    {
+        writeln!(writer, "timestamp,roll,pitch,yaw,heading")?;
+        let mut ticker = Ticker::new(interval);
+        for row in 0..count {
+            ticker.wait();
+            let timestamp = ::std::time::SystemTime::now()
+                .duration_since(::std::time::UNIX_EPOCH)
+                .unwrap_or_default();
+            let row_text = match if_ready(self.get_orientation())? {
+                Some(o) => {
+                    let (roll, pitch, yaw) = o.as_degrees();
+                    let heading = heading_degrees(true_heading(o.yaw, self.declination));
+                    format!("{:.2},{:.2},{:.2},{:.2}", roll, pitch, yaw, heading)
+                }
+                None => String::from(",,,"),
+            };
+            writeln!(
+                writer,
+                "{}.{:03},{}",
+                timestamp.as_secs(),
+                timestamp.subsec_millis(),
+                row_text
+            )?;
+            if row % 10 == 9 {
+                writer.flush()?;
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Returns an endless iterator which reads `get_environment` once every
+    /// `interval`, sleeping in between. The time taken by each read is
+    /// allowed for, so the rate stays steady. Use `take` to stop after a few
+    /// readings.
+    pub fn environment_stream<'s>(
+        &'s mut self,
+        interval: ::std::time::Duration,
+    ) -> EnvironmentStream<'s, 'a, D> {
+        EnvironmentStream {
+            hat: self,
+            ticker: Ticker::new(interval),
+        }
+    }
+
+    /// Samples every sensor, with `get_environment` and `read_imu`.
+    pub fn get_reading(&mut self) -> SenseHatResult<Reading> {
+        Ok(Reading {
+            timestamp: ::std::time::SystemTime::now(),
+            environment: self.get_environment()?,
+            imu: if_ready(self.read_imu())?,
+        })
+    }
+
+    /// Returns a vector representing the current orientation, using all
+    /// three sensors. Each `Angle` can be read in either unit with
+    /// `as_degrees()` or `as_radians()`; see also `get_orientation_degrees`.
+    pub fn get_orientation(&mut self) -> SenseHatResult<Orientation> {
+        self.set_imu_mode(ImuMode::Fusion);
+        if self.accelerometer_chip.imu_read() {
+            self.data = self.accelerometer_chip.get_imu_data()?;
+        }
+        match self.data.fusion_pose {
+            Some(o) => Ok(o),
+            None => Err(SenseHatError::NotReady(Subsystem::Imu)),
+        }
+    }
+
+    /// Returns how often to poll the IMU (with `get_orientation` and the
+    /// like), as recommended by `RTIMULib` for the sensors' sample rate.
+    /// The fusion algorithm works best when it sees every sample, so loops
+    /// should sleep for this long between readings; polling faster just
+    /// wastes CPU time.
+    pub fn recommended_poll_interval(&self) -> ::std::time::Duration {
+        self.accelerometer_chip.poll_interval()
+    }
+
+    /// Like `get_orientation`, but waits for a fresh sample from the IMU,
+    /// rather than returning the last one again, and gives up with
+    /// `SenseHatError::Timeout` if there isn't one within `timeout`. Use this
+    /// in a loop so that an IMU which has stopped producing data is noticed,
+    /// rather than the same old orientation coming back for ever.
+    pub fn get_orientation_timeout(
+        &mut self,
+        timeout: ::std::time::Duration,
+    ) -> SenseHatResult<Orientation> {
+        self.set_imu_mode(ImuMode::Fusion);
+        let start = ::std::time::Instant::now();
+        loop {
+            if self.accelerometer_chip.imu_read() {
+                self.data = self.accelerometer_chip.get_imu_data()?;
+                if let Some(o) = self.data.fusion_pose {
+                    return Ok(o);
+                }
+            }
+            if start.elapsed() >= timeout {
+                return Err(SenseHatError::Timeout(Subsystem::Imu, timeout));
+            }
+            ::std::thread::sleep(::std::time::Duration::from_millis(2));
+        }
+    }
+
+    /// Returns the current orientation from `get_orientation`, as `(roll,
+    /// pitch, yaw)` in degrees.
+    pub fn get_orientation_degrees(&mut self) -> SenseHatResult<(f64, f64, f64)> {
+        self.get_orientation().map(|o| o.as_degrees())
+    }
+
+    /// Sets the local magnetic declination, in degrees: how far east of true
+    /// north a compass points (use a negative number if it points west). It
+    /// is added to the headings from `get_compass`, `get_compass_heading` and
+    /// `get_compass_tilt_compensated`, so they are from true north rather
+    /// than magnetic north. The default is zero.
+    ///
+    /// The declination depends on where you are, and drifts slowly over the
+    /// years. NOAA's calculator at
+    /// <https://www.ngdc.noaa.gov/geomag/calculators/magcalc.shtml> gives
+    /// the current value for any latitude and longitude.
+    pub fn set_declination(&mut self, degrees: f64) {
+        self.declination = degrees;
+    }
+
+    /// Get the compass heading (ignoring gyro and accelerometer), as an
+    /// `Angle` from -180 to 180 degrees. The declination from
+    /// `set_declination` is applied.
+    ///
+    /// This takes the IMU out of fusion mode, and `RTIMULib` has to settle
+    /// again when it goes back, so calling this in between calls to
+    /// `get_orientation` makes the orientation worse. The raw readings
+    /// (`get_compass_raw` and friends) don't have this problem.
+    pub fn get_compass(&mut self) -> SenseHatResult<Angle> {
+        self.set_imu_mode(ImuMode::CompassOnly);
+        if self.accelerometer_chip.imu_read() {
+            // Don't cache this data
+            let data = self.accelerometer_chip.get_imu_data()?;
+            match data.fusion_pose {
+                Some(o) => Ok(true_heading(o.yaw, self.declination)),
+                None => Err(SenseHatError::NotReady(Subsystem::Imu)),
+            }
+        } else {
+            Err(SenseHatError::NotReady(Subsystem::Imu))
+        }
+    }
+
+    /// Returns the compass heading from `get_compass` in degrees, from 0
+    /// (inclusive) to 360 (exclusive), rather than -180 to 180.
+    pub fn get_compass_heading(&mut self) -> SenseHatResult<f64> {
+        self.get_compass().map(heading_degrees)
+    }
+
+    /// Returns the compass heading worked out from `get_compass_raw`,
+    /// corrected for the board's tilt using `get_accel_raw`, so it is right
+    /// even when the Sense HAT isn't level. Like `get_compass`, this is from
+    /// -180 to 180 degrees.
+    pub fn get_compass_tilt_compensated(&mut self) -> SenseHatResult<Angle> {
+        let compass = self.get_compass_raw()?;
+        let accel = self.get_accel_raw()?;
+        let (_, _, yaw) = absolute_pose(accel, compass);
+        Ok(true_heading(Angle::from_radians(yaw), self.declination))
+    }
+
+    /// Returns a vector representing the magnetic field measured by the
+    /// magnetometer, in microteslas (µT).
+    ///
+    /// The hard-iron offsets from `set_compass_calibration` are subtracted.
+    pub fn get_compass_raw(&mut self) -> SenseHatResult<Vector3D> {
+        let c = self.get_compass_raw_uncalibrated()?;
+        match self.compass_calibration {
+            Some(ref cal) => Ok(c - cal.offset()),
+            None => Ok(c),
+        }
+    }
+
+    /// Samples the magnetometer for `duration`, while you rotate the Sense
+    /// HAT through every orientation, and works out its hard-iron offsets.
+    /// `progress` is called with the calibration so far after every sample,
+    /// so you can show how it's going.
+    ///
+    /// Store the result and pass it to `set_compass_calibration` to have it
+    /// applied to future calls to `get_compass_raw`. The fused `get_compass`
+    /// heading comes from RTIMULib, which uses the calibration in its own
+    /// settings file instead (see `with_imu_settings`).
+    pub fn calibrate_compass<F>(
+        &mut self,
+        duration: ::std::time::Duration,
+        mut progress: F,
+    ) -> SenseHatResult<CompassCalibration>
+    where
+        F: FnMut(&CompassCalibration),
+    {
+        let start = ::std::time::Instant::now();
+        let mut calibration: Option<CompassCalibration> = None;
+        while start.elapsed() < duration {
+            match self.get_compass_raw_uncalibrated() {
+                Ok(c) => {
+                    let cal = match calibration {
+                        Some(cal) => cal.include(c),
+                        None => CompassCalibration { min: c, max: c },
+                    };
+                    progress(&cal);
+                    calibration = Some(cal);
+                }
+                Err(SenseHatError::NotReady(_)) => {}
+                Err(e) => return Err(e),
+            }
+            ::std::thread::sleep(::std::time::Duration::from_millis(10));
+        }
+        calibration.ok_or(SenseHatError::NotReady(Subsystem::Imu))
+    }
+
+    /// Sets the hard-iron calibration for the magnetometer, as produced by
+    /// `calibrate_compass`, or clears it if `None`.
+    pub fn set_compass_calibration(&mut self, calibration: Option<CompassCalibration>) {
+        self.compass_calibration = calibration;
+    }
+
+    fn get_compass_raw_uncalibrated(&mut self) -> SenseHatResult<Vector3D> {
+        self.set_imu_mode(ImuMode::Fusion);
+        if self.accelerometer_chip.imu_read() {
+            self.data = self.accelerometer_chip.get_imu_data()?;
+        }
+        match self.data.compass {
+            Some(c) => Ok(c),
+            None => Err(SenseHatError::NotReady(Subsystem::Imu)),
+        }
+    }
+
+    /// Returns a vector representing the current orientation using only
+    /// the gyroscope. This is an orientation, made of `Angle`s; for the rate
+    /// of rotation in radians per second, use `get_gyro_raw`.
+    pub fn get_gyro(&mut self) -> SenseHatResult<Orientation> {
+        self.set_imu_mode(ImuMode::GyroOnly);
+        if self.accelerometer_chip.imu_read() {
+            let data = self.accelerometer_chip.get_imu_data()?;
+            match data.fusion_pose {
+                Some(o) => Ok(o),
+                None => Err(SenseHatError::NotReady(Subsystem::Imu)),
+            }
+        } else {
+            Err(SenseHatError::NotReady(Subsystem::Imu))
+        }
+    }
+
+    /// Returns the current orientation from `get_gyro`, as `(roll, pitch,
+    /// yaw)` in degrees.
+    pub fn get_gyro_degrees(&mut self) -> SenseHatResult<(f64, f64, f64)> {
+        self.get_gyro().map(|o| o.as_degrees())
+    }
+
+    /// Returns a vector representing the current orientation using only
+    /// the accelerometer. This is an orientation, made of `Angle`s; for the
+    /// acceleration in Gs, use `get_accel_raw`.
+    pub fn get_accel(&mut self) -> SenseHatResult<Orientation> {
+        self.set_imu_mode(ImuMode::AccelOnly);
+        if self.accelerometer_chip.imu_read() {
+            let data = self.accelerometer_chip.get_imu_data()?;
+            match data.fusion_pose {
+                Some(o) => Ok(o),
+                None => Err(SenseHatError::NotReady(Subsystem::Imu)),
+            }
+        } else {
+            Err(SenseHatError::NotReady(Subsystem::Imu))
+        }
+    }
+
+    /// Returns the current orientation from `get_accel`, as `(roll, pitch,
+    /// yaw)` in degrees.
+    pub fn get_accel_degrees(&mut self) -> SenseHatResult<(f64, f64, f64)> {
+        self.get_accel().map(|o| o.as_degrees())
+    }
+
+    /// Returns a vector representing the current acceleration in Gs.
+    ///
+    /// The calibration from `set_accel_calibration` is applied.
+    pub fn get_accel_raw(&mut self) -> SenseHatResult<Vector3D> {
+        self.set_imu_mode(ImuMode::Fusion);
+        if self.accelerometer_chip.imu_read() {
+            self.data = self.accelerometer_chip.get_imu_data()?;
+        }
+        match self.data.accel {
+            Some(a) => Ok((a - self.accel_offset).scaled(&self.accel_scale)),
+            None => Err(SenseHatError::NotReady(Subsystem::Imu)),
+        }
+    }
+
+    /// Returns the acceleration from `get_accel_raw`, with the x and y axes
+    /// turned to match the LED matrix picture after `set_rotation`. When the
+    /// picture is the right way up for the viewer, so is this: gravity
+    /// pulls towards the bottom of the picture along -y.
+    pub fn get_accel_raw_display_frame(&mut self) -> SenseHatResult<Vector3D> {
+        let a = self.get_accel_raw()?;
+        Ok(rotate_vector(a, self.rotation))
+    }
+
+    /// Sets a per-axis calibration for the accelerometer, so that
+    /// `get_accel_raw` returns `(raw - offset) * scale` on each axis.
+    ///
+    /// To find them, hold each axis pointing straight up and then straight
+    /// down, and note the readings: the offset is their average, and the
+    /// scale is 2 divided by their difference. An offset of zero and a scale
+    /// of one on every axis turns the calibration off again.
+    pub fn set_accel_calibration(&mut self, offset: Vector3D, scale: Vector3D) {
+        self.accel_offset = offset;
+        self.accel_scale = scale;
+    }
+
+    /// Returns true if the Sense HAT is being shaken or tapped: that is, if
+    /// the acceleration from `get_accel_raw` differs from the 1 G of gravity
+    /// by more than `threshold_g`. Around 0.5 G catches a deliberate shake
+    /// without reacting to the board being picked up.
+    pub fn detect_shake(&mut self, threshold_g: f64) -> SenseHatResult<bool> {
+        Ok(is_shaking(self.get_accel_raw()?, threshold_g))
+    }
+
+    /// Returns true if the Sense HAT is level: that is, if the roll and
+    /// pitch worked out from gravity in `get_accel_raw` are both within
+    /// `tolerance` of zero. An upside-down board isn't level. The reading is
+    /// only meaningful while the board is still.
+    pub fn is_level(&mut self, tolerance: Angle) -> SenseHatResult<bool> {
+        Ok(is_level(self.get_accel_raw()?, tolerance))
+    }
+
+    /// Waits until `detect_shake` sees a shake, checking every 10 ms.
+    /// Returns false if there wasn't one within `timeout`.
+    pub fn wait_for_shake(
+        &mut self,
+        threshold_g: f64,
+        timeout: ::std::time::Duration,
+    ) -> SenseHatResult<bool> {
+        let start = ::std::time::Instant::now();
+        loop {
+            match self.detect_shake(threshold_g) {
+                Ok(true) => return Ok(true),
+                Ok(false) | Err(SenseHatError::NotReady(_)) => {}
+                Err(e) => return Err(e),
+            }
+            if start.elapsed() >= timeout {
+                return Ok(false);
+            }
+            ::std::thread::sleep(::std::time::Duration::from_millis(10));
+        }
+    }
+
+    /// Returns a vector representing the current acceleration in metres per
+    /// second squared (taking 1 G to be 9.80665 m/s²).
+    pub fn get_accel_raw_ms2(&mut self) -> SenseHatResult<Vector3D> {
+        let a = self.get_accel_raw()?;
+        Ok(Vector3D {
+            x: a.x * STANDARD_GRAVITY,
+            y: a.y * STANDARD_GRAVITY,
+            z: a.z * STANDARD_GRAVITY,
+        })
+    }
+
+    /// Returns a vector representing the current angular velocity around
+    /// each axis, in radians per second.
+    pub fn get_gyro_raw(&mut self) -> SenseHatResult<Vector3D> {
+        self.set_imu_mode(ImuMode::Fusion);
+        if self.accelerometer_chip.imu_read() {
+            self.data = self.accelerometer_chip.get_imu_data()?;
+        }
+        match self.data.gyro {
+            Some(g) => Ok(g),
+            None => Err(SenseHatError::NotReady(Subsystem::Imu)),
+        }
+    }
+
+    /// Reads the IMU once, with all three sensors enabled, and returns
+    /// everything it measured. Unlike calling `get_orientation`,
+    /// `get_gyro_raw`, `get_accel_raw` and `get_compass_raw` in turn, all the
+    /// values come from the same instant.
+    ///
+    /// If the IMU has no new data, the previous sample is returned again.
+    pub fn read_imu(&mut self) -> SenseHatResult<ImuReading> {
+        self.set_imu_mode(ImuMode::Fusion);
+        if self.accelerometer_chip.imu_read() {
+            self.data = self.accelerometer_chip.get_imu_data()?;
+        }
+        if self.data.timestamp == 0 {
+            return Err(SenseHatError::NotReady(Subsystem::Imu));
+        }
+        Ok(ImuReading {
+            timestamp: self.data.timestamp,
+            fusion_pose: self.data.fusion_pose,
+            gyro: self.data.gyro,
+            accel: self.data.accel,
+            compass: self.data.compass,
+        })
+    }
+
+    /// Enables the sensors for `mode` on the IMU, unless they already are.
+    fn set_imu_mode(&mut self, mode: ImuMode) {
+        if self.imu_mode != Some(mode) {
+            debug!("IMU mode {:?} -> {:?}", self.imu_mode, mode);
+            self.accelerometer_chip.set_mode(mode);
+            self.imu_mode = Some(mode);
+        }
+    }
+
+    /// Returns the timestamp, in microseconds, of the IMU sample most
+    /// recently fetched by `read_imu`, `get_orientation`, `get_accel_raw`,
+    /// `get_gyro_raw` or `get_compass_raw`. Use the difference between two
+    /// timestamps as the time between samples. Returns 0 if no sample has
+    /// been fetched yet.
+    pub fn get_imu_timestamp(&self) -> u64 {
+        self.data.timestamp
+    }
+
+    /// Sets the fonts used to render text on the LED matrix.
+    ///
+    /// The default collection contains the basic latin, latin-1, block, box,
+    /// greek and hiragana glyphs from the `font8x8` crate. You can build your
+    /// own with `FontCollection::from_hashmap`, mapping each `char` to a
+    /// `FontUnicode` holding its 8x8 bitmap. Characters which are not in the
+    /// collection are skipped when the text is rendered.
+    #[cfg(feature = "led-matrix")]
+    pub fn set_font(&mut self, fonts: FontCollection) {
+        self.fonts = fonts;
+    }
+
+    /// Returns every character the current fonts can draw, in order, so
+    /// you can check text before you show it. `FontCollection` can't list
+    /// its glyphs, so this checks the characters the bundled `font8x8` sets
+    /// have glyphs for (basic, Latin, box drawing, block, Greek and
+    /// hiragana). A font from `set_font` with other characters can still
+    /// draw them, but they aren't listed.
+    #[cfg(feature = "led-matrix")]
+    pub fn supported_characters(&self) -> Vec<char> {
+        use sensehat_screen::fonts::{
+            UnicodeFonts, BASIC_FONTS, BLOCK_FONTS, BOX_FONTS, GREEK_FONTS, HIRAGANA_FONTS,
+            LATIN_FONTS,
+        };
+        let mut chars: Vec<char> = BASIC_FONTS
+            .iter()
+            .chain(LATIN_FONTS.iter())
+            .chain(BOX_FONTS.iter())
+            .chain(BLOCK_FONTS.iter())
+            .chain(GREEK_FONTS.iter())
+            .chain(HIRAGANA_FONTS.iter())
+            .map(|font| font.char())
+            .filter(|&c| self.fonts.contains_key(c))
+            .collect();
+        chars.sort_unstable();
+        chars.dedup();
+        chars
+    }
+
+    /// Opens the joystick. By default this is whichever `/dev/input/eventN`
+    /// the kernel calls "Raspberry Pi Sense HAT Joystick".
+    pub fn joystick(&self) -> SenseHatResult<Joystick> {
+        let path = match self.joystick {
+            Some(ref path) => ::std::path::PathBuf::from(path),
+            None => SenseHat::detect_joystick_device()?,
+        };
+        Joystick::open(path).map_err(SenseHatError::JoystickError)
+    }
+
+    /// Opens the joystick, as `joystick` does, and returns a stream of its
+    /// events for async code. A thread reads the events until the stream is
+    /// dropped.
+    #[cfg(feature = "async")]
+    pub fn joystick_stream(&self) -> SenseHatResult<JoystickStream> {
+        self.joystick().and_then(JoystickStream::new)
+    }
+
+    /// Uses the given input device (e.g. `/dev/input/event2`) for the
+    /// joystick, rather than detecting it.
+    pub fn set_joystick_path(&mut self, path: &str) {
+        self.joystick = Some(String::from(path));
+    }
+
+    /// Returns the path of the LED matrix framebuffer device. By default this
+    /// is whichever `/dev/fbN` the kernel calls "RPi-Sense FB", or
+    /// `/dev/fb1` if there isn't one.
+    #[cfg(feature = "led-matrix")]
+    pub fn framebuffer_path(&self) -> &str {
+        &self.framebuffer
+    }
+
+    /// Uses the given framebuffer device (e.g. `/dev/fb0`) for the LED
+    /// matrix, rather than the one that was detected.
+    #[cfg(feature = "led-matrix")]
+    pub fn set_framebuffer_path(&mut self, path: &str) {
+        self.framebuffer = String::from(path);
+        self.screen = None;
+    }
+
+    /// Displays a scrolling message on the LED matrix. Blocks until the
+    /// entire message has scrolled past.
     ///
     /// The `interval` can be a `std::time::Duration` or an `Fps` (frames per
     /// second).
@@ -299,36 +2178,330 @@ impl<'a> SenseHat<'a> {
     /// * a string from the [W3C basic keywords](https://www.w3.org/TR/css-color-3/#html4) like `"white"` or `"purple"`, or
     /// * an RGB 8-bit triple like `(0, 0xFF, 0)`.
     #[cfg(feature = "led-matrix")]
-    pub fn show_message<INT, FG, BG>(
+    pub fn show_message<INT, FG, BG>(
+        &mut self,
+        message: &str,
+        interval: INT,
+        fg: FG,
+        bg: BG,
+    ) -> SenseHatResult<()>
+    where
+        INT: Into<::std::time::Duration>,
+        FG: Into<Colour>,
+        BG: Into<Colour>,
+    {
+        let pixel_frames = self.render_message(message, fg, bg)?;
+        self.scroll_frames(&pixel_frames, interval)
+    }
+
+    /// Displays a scrolling message on the LED matrix, like `show_message`,
+    /// but stops as soon as the joystick is pressed. Returns the press that
+    /// stopped it, or `None` if the whole message scrolled past.
+    ///
+    /// Only `Action::Pressed` events count, so letting go of the joystick
+    /// from an earlier press doesn't dismiss the message.
+    #[cfg(feature = "led-matrix")]
+    pub fn show_message_until_press<INT, FG, BG>(
+        &mut self,
+        message: &str,
+        interval: INT,
+        fg: FG,
+        bg: BG,
+    ) -> SenseHatResult<Option<JoystickEvent>>
+    where
+        INT: Into<::std::time::Duration>,
+        FG: Into<Colour>,
+        BG: Into<Colour>,
+    {
+        let (fg, bg) = (fg.into(), bg.into());
+        let mut joystick = self.joystick()?;
+        let sanitized = self.fonts.sanitize_str(message)?;
+        let pixel_frames = sanitized.pixel_frames(fg.0, bg.0);
+        let mut press = None;
+        self.scroll_until(&pixel_frames, interval.into(), || {
+            while let Some(event) = joystick.try_read_event().map_err(joystick_read_error)? {
+                if event.action == Action::Pressed {
+                    press = Some(event);
+                    return Ok(true);
+                }
+            }
+            Ok(false)
+        })?;
+        Ok(press)
+    }
+
+    /// Scrolls a message across the LED matrix forever, like a ticker, with
+    /// `gap` columns of background between the end of the message and the
+    /// start of the next repeat. The frames are worked out once up front, so
+    /// there's no pause where it wraps around, unlike calling `show_message`
+    /// in a loop. This only returns if there's an error.
+    #[cfg(feature = "led-matrix")]
+    pub fn marquee<INT, FG, BG>(
+        &mut self,
+        message: &str,
+        interval: INT,
+        fg: FG,
+        bg: BG,
+        gap: usize,
+    ) -> SenseHatResult<()>
+    where
+        INT: Into<::std::time::Duration>,
+        FG: Into<Colour>,
+        BG: Into<Colour>,
+    {
+        let (fg, bg) = (fg.into(), bg.into());
+        let interval = interval.into();
+        let sanitized = self.fonts.sanitize_str(message)?;
+        let mut columns = Vec::new();
+        for frame in sanitized.pixel_frames(fg.0, bg.0) {
+            let bytes = frame.frame_line().as_bytes();
+            for x in 0..8 {
+                let mut column = [[0u8; 2]; 8];
+                for (y, pixel) in column.iter_mut().enumerate() {
+                    let offset = (y * 8 + x) * 2;
+                    pixel.copy_from_slice(&bytes[offset..offset + 2]);
+                }
+                columns.push(column);
+            }
+        }
+        columns.extend(::std::iter::repeat_n([bg.0.rgb565(); 8], gap));
+        if columns.is_empty() {
+            return Ok(());
+        }
+        let frames: Vec<[u8; 128]> = (0..columns.len())
+            .map(|start| rotate_frame(&marquee_frame(&columns, start), self.rotation))
+            .collect();
+        let screen = self.screen()?;
+        loop {
+            for frame in &frames {
+                screen.write_frame(frame);
+                ::std::thread::sleep(interval);
+            }
+        }
+    }
+
+    /// Shows a simple menu, driven by the joystick. The selected item scrolls
+    /// across the LED matrix over and over; push the joystick up or down to
+    /// move to the previous or next item, and press it in to choose. Returns
+    /// the index of the chosen item, or `None` if the joystick isn't touched
+    /// for `timeout` (or `items` is empty). The matrix is cleared afterwards.
+    ///
+    /// Only `Action::Pressed` events count, so holding the joystick moves one
+    /// item at a time.
+    #[cfg(feature = "led-matrix")]
+    pub fn menu<FG, BG>(
+        &mut self,
+        items: &[&str],
+        fg: FG,
+        bg: BG,
+        timeout: ::std::time::Duration,
+    ) -> SenseHatResult<Option<usize>>
+    where
+        FG: Into<Colour>,
+        BG: Into<Colour>,
+    {
+        if items.is_empty() {
+            return Ok(None);
+        }
+        let (fg, bg) = (fg.into(), bg.into());
+        let mut joystick = self.joystick()?;
+        let mut selected = 0;
+        let mut chosen = None;
+        let mut last_input = ::std::time::Instant::now();
+        loop {
+            let sanitized = self.fonts.sanitize_str(items[selected])?;
+            let pixel_frames = sanitized.pixel_frames(fg.0, bg.0);
+            let mut next = selected;
+            self.scroll_until(
+                &pixel_frames,
+                ::std::time::Duration::from_millis(100),
+                || {
+                    while let Some(event) =
+                        joystick.try_read_event().map_err(joystick_read_error)?
+                    {
+                        if event.action != Action::Pressed {
+                            continue;
+                        }
+                        last_input = ::std::time::Instant::now();
+                        if event.direction == Direction::Middle {
+                            chosen = Some(next);
+                            return Ok(true);
+                        }
+                        next = menu_step(next, items.len(), event.direction);
+                    }
+                    // Redraw as soon as the selection changes
+                    Ok(next != selected || last_input.elapsed() >= timeout)
+                },
+            )?;
+            if chosen.is_some() || last_input.elapsed() >= timeout {
+                self.clear()?;
+                return Ok(chosen);
+            }
+            selected = next;
+        }
+    }
+
+    /// Displays a scrolling message on the LED matrix, where each segment of
+    /// the message has its own foreground and background colour. Blocks until
+    /// the entire message has scrolled past.
+    ///
+    /// The `interval` can be a `std::time::Duration` or an `Fps` (frames per
+    /// second).
+    ///
+    /// Each segment is a `(message, fg, bg)` tuple, e.g.
+    /// `&[("21.5C", Colour::GREEN, Colour::BLACK), ("!", Colour::RED, Colour::BLACK)]`.
+    #[cfg(feature = "led-matrix")]
+    pub fn text_coloured<INT>(
+        &mut self,
+        segments: &[(&str, Colour, Colour)],
+        interval: INT,
+    ) -> SenseHatResult<()>
+    where
+        INT: Into<::std::time::Duration>,
+    {
+        let mut pixel_frames = Vec::new();
+        for &(message, fg, bg) in segments {
+            let sanitized = self.fonts.sanitize_str(message)?;
+            pixel_frames.extend(sanitized.pixel_frames(fg.0, bg.0));
+        }
+        self.scroll_frames(&pixel_frames, interval)
+    }
+
+    /// Returns the LED matrix framebuffer, opening it the first time.
+    #[cfg(feature = "led-matrix")]
+    fn screen(&mut self) -> SenseHatResult<&mut sensehat_screen::framebuffer::Framebuffer> {
+        if self.screen.is_none() {
+            let file = self.check_framebuffer()?;
+            let format = screen_info::read_format(&file)
+                .map_err(|err| SenseHatError::FramebufferError(self.framebuffer.clone(), err))?;
+            if format != LED_MATRIX_FORMAT {
+                return Err(SenseHatError::WrongFramebufferFormat(
+                    self.framebuffer.clone(),
+                    format,
+                ));
+            }
+            debug!("Opening framebuffer {}", self.framebuffer);
+            let framebuffer = sensehat_screen::framebuffer::Framebuffer::new(&self.framebuffer)?;
+            self.screen = Some(framebuffer);
+        }
+        match self.screen {
+            Some(ref mut screen) => Ok(screen),
+            None => unreachable!(),
+        }
+    }
+
+    /// Returns the size and pixel depth that the framebuffer device reports,
+    /// from the `FBIOGET_VSCREENINFO` ioctl. This fails if the device isn't
+    /// a framebuffer.
+    #[cfg(feature = "led-matrix")]
+    pub fn framebuffer_format(&self) -> SenseHatResult<FramebufferFormat> {
+        ::std::fs::File::open(&self.framebuffer)
+            .and_then(|file| screen_info::read_format(&file))
+            .map_err(|err| SenseHatError::FramebufferError(self.framebuffer.clone(), err))
+    }
+
+    /// `sensehat_screen` only gives us a message when it can't open the
+    /// framebuffer, so try opening it ourselves first to get a proper
+    /// `io::Error`. The format is checked on the file this returns.
+    #[cfg(feature = "led-matrix")]
+    fn check_framebuffer(&self) -> SenseHatResult<::std::fs::File> {
+        ::std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&self.framebuffer)
+            .map_err(|err| SenseHatError::FramebufferError(self.framebuffer.clone(), err))
+    }
+
+    /// Renders a message into pixel frames, one per character, ready for
+    /// `scroll_frames`. Keep the frames to show the same message again
+    /// without rendering it every time.
+    #[cfg(feature = "led-matrix")]
+    pub fn render_message<FG, BG>(
+        &self,
+        message: &str,
+        fg: FG,
+        bg: BG,
+    ) -> SenseHatResult<Vec<PixelFrame>>
+    where
+        FG: Into<Colour>,
+        BG: Into<Colour>,
+    {
+        let (fg, bg) = (fg.into(), bg.into());
+        let sanitized = self.fonts.sanitize_str(message)?;
+        Ok(sanitized.pixel_frames(fg.0, bg.0))
+    }
+
+    /// Renders a message as the steps of scrolling it right-to-left across
+    /// the LED matrix, like `text` does, but without showing them. Show
+    /// each one in turn with `show_frame`, as often as you like, to scroll
+    /// the message while doing other work in between, such as polling the
+    /// joystick. A message of one character is a single frame, which
+    /// doesn't scroll.
+    #[cfg(feature = "led-matrix")]
+    pub fn text_frames<FG, BG>(
         &mut self,
         message: &str,
-        interval: INT,
         fg: FG,
         bg: BG,
-    ) -> SenseHatResult<()>
+    ) -> SenseHatResult<Vec<PixelFrame>>
     where
-        INT: Into<::std::time::Duration>,
         FG: Into<Colour>,
         BG: Into<Colour>,
     {
-        // Calculate our waiting time for each frame
-        let wait_time = interval.into();
+        let pixel_frames = self.render_message(message, fg, bg)?;
+        Ok(scroll_steps(&pixel_frames))
+    }
+
+    /// Shows a single pixel frame on the LED matrix, such as one of those
+    /// from `text_frames`.
+    #[cfg(feature = "led-matrix")]
+    pub fn show_frame(&mut self, frame: &PixelFrame) -> SenseHatResult<()> {
+        self.write_frame_bytes(&frame.frame_line().as_bytes())
+    }
+
+    /// Scrolls the given pixel frames right-to-left across the LED matrix,
+    /// waiting `interval` between each step. Blocks until they have all
+    /// scrolled past. Use it with `render_message`, or with frames you've
+    /// drawn yourself.
+    ///
+    /// The framebuffer is opened before anything is drawn, so if this fails
+    /// the matrix is left as it was.
+    #[cfg(feature = "led-matrix")]
+    pub fn scroll_frames<INT>(
+        &mut self,
+        pixel_frames: &[PixelFrame],
+        interval: INT,
+    ) -> SenseHatResult<()>
+    where
+        INT: Into<::std::time::Duration>,
+    {
+        self.scroll_until(pixel_frames, interval.into(), || Ok(false))?;
+        Ok(())
+    }
+
+    /// Scrolls the frames across the LED matrix, calling `stop` after each
+    /// one and giving up early (returning true) if it returns true.
+    #[cfg(feature = "led-matrix")]
+    fn scroll_until<F>(
+        &mut self,
+        pixel_frames: &[PixelFrame],
+        wait_time: ::std::time::Duration,
+        mut stop: F,
+    ) -> SenseHatResult<bool>
+    where
+        F: FnMut() -> SenseHatResult<bool>,
+    {
+        let rotation = self.rotation;
         // Connect to our LED Matrix screen.
-        let mut screen = sensehat_screen::Screen::open("/dev/fb1")?;
-        // Get the default `FontCollection`.
-        let fonts = sensehat_screen::FontCollection::new();
-        // Create a sanitized `FontString`.
-        let sanitized = fonts.sanitize_str(message)?;
-        // Render the `FontString` as a vector of pixel frames.
-        let pixel_frames = sanitized.pixel_frames(fg.into().0, bg.into().0);
-        // Create a `Scroll` from the pixel frame vector.
-        let scroll = sensehat_screen::Scroll::new(&pixel_frames);
-        // Consume the `FrameSequence` returned by the `right_to_left` method.
-        scroll.right_to_left().for_each(|frame| {
-            screen.write_frame(&frame.frame_line());
+        let screen = self.screen()?;
+        for frame in scroll_steps(pixel_frames) {
+            screen.write_frame(&rotate_frame(&frame.frame_line().as_bytes(), rotation));
             ::std::thread::sleep(wait_time);
-        });
-        Ok(())
+            if stop()? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
     }
 
     /// Displays a scrolling message on the LED matrix. Blocks until the
@@ -349,15 +2522,737 @@ impl<'a> SenseHat<'a> {
         self.show_message(message, ::std::time::Duration::from_millis(100), fg, bg)
     }
 
+    /// Starts drawing off-screen. Until `present` is called, drawing on the
+    /// LED matrix (with `set_pixel`, `draw_line`, `clear`,
+    /// `write_frame_bytes` and the like) changes a copy of what it is
+    /// showing, which `frame_bytes` returns, and nothing is shown. That
+    /// avoids the flicker of a frame built up one pixel at a time.
+    ///
+    /// Scrolling text and other animations still write straight to the
+    /// matrix. Calling this again before `present` carries on with the same
+    /// frame.
+    #[cfg(feature = "led-matrix")]
+    pub fn begin_frame(&mut self) -> SenseHatResult<()> {
+        if self.back_buffer.is_none() {
+            self.back_buffer = Some(self.frame_bytes()?);
+        }
+        Ok(())
+    }
+
+    /// Shows the frame drawn since `begin_frame`, in a single write, and
+    /// goes back to drawing straight to the LED matrix. Does nothing if
+    /// `begin_frame` hasn't been called.
+    #[cfg(feature = "led-matrix")]
+    pub fn present(&mut self) -> SenseHatResult<()> {
+        match self.back_buffer.take() {
+            Some(bytes) => self.write_frame_bytes(&bytes),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the raw contents of the LED matrix framebuffer, as 64 RGB565
+    /// pixels of two bytes each, row by row from the top-left (of the turned
+    /// picture, after `set_rotation`). Between `begin_frame` and `present`,
+    /// this is the frame being drawn instead.
+    #[cfg(feature = "led-matrix")]
+    pub fn frame_bytes(&mut self) -> SenseHatResult<[u8; 128]> {
+        if let Some(bytes) = self.back_buffer {
+            return Ok(bytes);
+        }
+        let mut bytes = [0u8; 128];
+        let rotation = self.rotation.inverse();
+        {
+            let frame = self.screen()?.read_frame();
+            if frame.len() >= 128 {
+                bytes.copy_from_slice(&frame[..128]);
+                return Ok(rotate_frame(&bytes, rotation));
+            }
+        }
+        Err(SenseHatError::FramebufferError(
+            self.framebuffer.clone(),
+            ::std::io::Error::new(
+                ::std::io::ErrorKind::UnexpectedEof,
+                "framebuffer is smaller than 8x8 pixels",
+            ),
+        ))
+    }
+
+    /// If `clear`, the LED matrix is blanked when this `SenseHat` is dropped
+    /// (for example when the program exits normally), rather than being
+    /// left showing whatever was drawn last. Off by default.
+    #[cfg(feature = "led-matrix")]
+    pub fn set_clear_on_drop(&mut self, clear: bool) {
+        self.clear_on_drop = clear;
+    }
+
+    /// Dims the LED matrix, by switching the framebuffer driver to its
+    /// low-light gamma table, or (if `low` is false) back to the default
+    /// table. This is the same as the Python library's `low_light`, and
+    /// lasts until it is changed again, even after this program exits.
+    #[cfg(feature = "led-matrix")]
+    pub fn set_low_light(&mut self, low: bool) -> SenseHatResult<()> {
+        gamma::set_low_light(::std::path::Path::new(&self.framebuffer), low)
+            .map_err(|err| SenseHatError::FramebufferError(self.framebuffer.clone(), err))
+    }
+
+    /// Returns true if the LED matrix is dimmed by `set_low_light`: that is,
+    /// if the framebuffer driver's gamma table is its low-light table.
+    #[cfg(feature = "led-matrix")]
+    pub fn low_light(&mut self) -> SenseHatResult<bool> {
+        gamma::read_gamma(::std::path::Path::new(&self.framebuffer))
+            .map(|table| gamma::is_low_light(&table))
+            .map_err(|err| SenseHatError::FramebufferError(self.framebuffer.clone(), err))
+    }
+
+    /// Turns everything drawn on the LED matrix from now on clockwise by
+    /// `rotation`, so it is the right way up when the Sense HAT is mounted
+    /// sideways or upside down. Pixel coordinates, `frame_bytes` and
+    /// `write_frame_bytes` all use the turned picture, as does
+    /// `get_accel_raw_display_frame`. What's already shown isn't redrawn.
+    pub fn set_rotation(&mut self, rotation: Rotation) {
+        self.rotation = rotation;
+    }
+
+    /// Renders what the LED matrix is showing as eight lines of text, for
+    /// checking drawing code without looking at the hardware. With `ansi`,
+    /// each pixel is two block characters in its own colour, using 24-bit
+    /// ANSI escapes; without, lit pixels are `#` and dark ones are `.`.
+    #[cfg(feature = "led-matrix")]
+    pub fn dump_frame_ascii(&mut self, ansi: bool) -> SenseHatResult<String> {
+        Ok(frame_to_string(&self.frame_bytes()?, ansi))
+    }
+
+    /// Writes raw RGB565 bytes directly to the LED matrix framebuffer, or to
+    /// the frame being drawn after `begin_frame`. The layout is the same as
+    /// that returned by `frame_bytes()`.
+    #[cfg(feature = "led-matrix")]
+    pub fn write_frame_bytes(&mut self, bytes: &[u8; 128]) -> SenseHatResult<()> {
+        if let Some(ref mut back_buffer) = self.back_buffer {
+            back_buffer.copy_from_slice(bytes);
+            return Ok(());
+        }
+        let bytes = rotate_frame(bytes, self.rotation);
+        self.screen()?.write_frame(&bytes);
+        Ok(())
+    }
+
+    /// Sets a single pixel on the LED matrix. `x` and `y` are in the range
+    /// `0..8`, with `(0, 0)` at the top-left.
+    ///
+    /// The pixel is shown straight away, unless this is between
+    /// `begin_frame` and `present`.
+    #[cfg(feature = "led-matrix")]
+    pub fn set_pixel<C>(&mut self, x: u8, y: u8, colour: C) -> SenseHatResult<()>
+    where
+        C: Into<Colour>,
+    {
+        if x >= 8 || y >= 8 {
+            return Err(SenseHatError::PixelOutOfRange);
+        }
+        let mut bytes = self.frame_bytes()?;
+        put_pixel(&mut bytes, i32::from(x), i32::from(y), colour.into());
+        self.write_frame_bytes(&bytes)
+    }
+
+    /// Draws a line from `(x0, y0)` to `(x1, y1)` inclusive, using
+    /// Bresenham's algorithm. Any part of the line that falls outside the
+    /// matrix is clipped.
+    #[cfg(feature = "led-matrix")]
+    pub fn draw_line<C>(
+        &mut self,
+        x0: i32,
+        y0: i32,
+        x1: i32,
+        y1: i32,
+        colour: C,
+    ) -> SenseHatResult<()>
+    where
+        C: Into<Colour>,
+    {
+        let mut bytes = self.frame_bytes()?;
+        put_line(&mut bytes, x0, y0, x1, y1, colour.into());
+        self.write_frame_bytes(&bytes)
+    }
+
+    /// Draws a `w` by `h` rectangle with its top-left corner at `(x, y)`,
+    /// either as an outline or `filled`. Any part of the rectangle that falls
+    /// outside the matrix is clipped.
+    #[cfg(feature = "led-matrix")]
+    pub fn draw_rect<C>(
+        &mut self,
+        x: i32,
+        y: i32,
+        w: i32,
+        h: i32,
+        colour: C,
+        filled: bool,
+    ) -> SenseHatResult<()>
+    where
+        C: Into<Colour>,
+    {
+        let mut bytes = self.frame_bytes()?;
+        put_rect(&mut bytes, x, y, w, h, colour.into(), filled);
+        self.write_frame_bytes(&bytes)
+    }
+
+    /// Shows a number from 0 to 99 on the LED matrix, without scrolling,
+    /// using a small 3x5 pixel font.
+    #[cfg(feature = "led-matrix")]
+    pub fn show_number<FG, BG>(&mut self, value: u8, fg: FG, bg: BG) -> SenseHatResult<()>
+    where
+        FG: Into<Colour>,
+        BG: Into<Colour>,
+    {
+        if value > 99 {
+            return Err(SenseHatError::NumberOutOfRange(value));
+        }
+        let mut bytes = [0u8; 128];
+        digits::render_number(&mut bytes, value, fg.into(), bg.into());
+        self.write_frame_bytes(&bytes)
+    }
+
+    /// Shows a bar graph on the LED matrix, lighting the bottom rows in
+    /// proportion to `fraction` (from 0.0 for none to 1.0 for all eight)
+    /// and turning the rest off. Fractions outside that range are clamped.
+    #[cfg(feature = "led-matrix")]
+    pub fn show_bar<C>(&mut self, fraction: f32, colour: C) -> SenseHatResult<()>
+    where
+        C: Into<Colour>,
+    {
+        let colour = colour.into();
+        let mut bytes = [0u8; 128];
+        for row in 8 - bar_rows(fraction)..8 {
+            put_line(&mut bytes, 0, row, 7, row, colour);
+        }
+        self.write_frame_bytes(&bytes)
+    }
+
+    /// Shows an RGB image on the LED matrix. `data` is `width * height`
+    /// pixels, row by row from the top-left, three bytes (red, green, blue)
+    /// each: the layout of an `image::RgbImage`, so you can pass
+    /// `img.width(), img.height(), img.as_raw()`. It takes the raw bytes,
+    /// rather than an `RgbImage`, so that this crate doesn't need to depend
+    /// on `image`, and so that frames from other sources (plotters, text
+    /// rasterizers) don't have to be copied into one first.
+    ///
+    /// Images that aren't 8x8 are resized with a box filter: each LED shows
+    /// the average of the pixels in its eighth of the width and height (or
+    /// the nearest pixel, for images smaller than 8x8).
+    #[cfg(feature = "led-matrix")]
+    pub fn show_rgb_buffer(&mut self, width: u32, height: u32, data: &[u8]) -> SenseHatResult<()> {
+        let bytes = resize_rgb(width, height, data)?;
+        self.write_frame_bytes(&bytes)
+    }
+
+    /// Fills the LED matrix with a diagonal rainbow. `phase` shifts the
+    /// colours along, in whole turns of the colour wheel, so increasing it a
+    /// little each frame (or setting it from a sensor, like the compass
+    /// heading divided by 360) makes the rainbow move. Handy for checking
+    /// that every LED works.
+    #[cfg(feature = "led-matrix")]
+    pub fn show_rainbow(&mut self, phase: f32) -> SenseHatResult<()> {
+        let mut bytes = [0u8; 128];
+        for y in 0..8 {
+            for x in 0..8 {
+                let hue = ((x + y) as f32 / 14.0 + phase) * 360.0;
+                put_pixel(&mut bytes, x, y, Colour::from_hsv(hue, 1.0, 1.0));
+            }
+        }
+        self.write_frame_bytes(&bytes)
+    }
+
+    /// Fills the LED matrix to show at a glance whether `value` is within
+    /// `low` to `high` (inclusive): green if it is, amber if it is within
+    /// a tenth of that range of either end (inside or out), and red if it
+    /// is further outside. A NaN `value` shows red.
+    #[cfg(feature = "led-matrix")]
+    pub fn show_threshold(&mut self, value: f64, low: f64, high: f64) -> SenseHatResult<()> {
+        let colour = threshold_colour(value, low, high).0.rgb565();
+        let mut bytes = [0u8; 128];
+        for pixel in bytes.chunks_mut(2) {
+            pixel.copy_from_slice(&colour);
+        }
+        self.write_frame_bytes(&bytes)
+    }
+
     /// Clears the LED matrix
     #[cfg(feature = "led-matrix")]
     pub fn clear(&mut self) -> SenseHatResult<()> {
-        // Connect to our LED Matrix screen.
-        let mut screen = sensehat_screen::Screen::open("/dev/fb1")?;
         // Send a blank image to clear the screen
         const OFF: [u8; 128] = [0x00; 128];
-        screen.write_frame(&sensehat_screen::FrameLine::from_slice(&OFF));
-        Ok(())
+        self.write_frame_bytes(&OFF)
+    }
+}
+
+/// Finds the Sense HAT's framebuffer device, by looking for the one named
+/// "RPi-Sense FB" in `sysfs` (normally `/sys/class/graphics`).
+#[cfg(feature = "led-matrix")]
+fn detect_framebuffer(sysfs: &::std::path::Path) -> Option<String> {
+    for entry in ::std::fs::read_dir(sysfs).ok()? {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(_) => continue,
+        };
+        let name = match entry.file_name().into_string() {
+            Ok(ref name) if name.starts_with("fb") => name.clone(),
+            _ => continue,
+        };
+        if let Ok(fb_name) = ::std::fs::read_to_string(entry.path().join("name")) {
+            if fb_name.trim() == "RPi-Sense FB" {
+                return Some(format!("/dev/{}", name));
+            }
+        }
+    }
+    None
+}
+
+/// The format of the LED matrix framebuffer.
+#[cfg(feature = "led-matrix")]
+const LED_MATRIX_FORMAT: FramebufferFormat = FramebufferFormat {
+    width: 8,
+    height: 8,
+    bits_per_pixel: 16,
+};
+
+/// Opens the IMU with `RTIMULib`, or, with the `native-imu` feature and
+/// without `rtimu`, with the pure-Rust driver on the given I2C bus.
+#[cfg(any(feature = "rtimu", not(feature = "native-imu")))]
+fn open_imu<'a>(
+    _path: &str,
+    imu_settings: Option<&::std::path::Path>,
+) -> SenseHatResult<Box<dyn ImuBackend + 'a>> {
+    Ok(Box::new(lsm9ds1::Lsm9ds1::new(imu_settings)?))
+}
+
+/// Opens the IMU with `RTIMULib`, or, with the `native-imu` feature and
+/// without `rtimu`, with the pure-Rust driver on the given I2C bus.
+#[cfg(all(feature = "native-imu", not(feature = "rtimu")))]
+fn open_imu<'a>(
+    path: &str,
+    _imu_settings: Option<&::std::path::Path>,
+) -> SenseHatResult<Box<dyn ImuBackend + 'a>> {
+    let ag_dev = LinuxI2CDevice::new(path, lsm9ds1_native::AG_I2C_ADDRESS)?;
+    let mag_dev = LinuxI2CDevice::new(path, lsm9ds1_native::MAG_I2C_ADDRESS)?;
+    Ok(Box::new(lsm9ds1_native::Lsm9ds1::new(ag_dev, mag_dev)?))
+}
+
+/// Works out (roll, pitch, yaw) in radians from the accelerometer (in any
+/// units) and the magnetometer, tilt-compensating the heading.
+fn absolute_pose(accel: Vector3D, compass: Vector3D) -> (f64, f64, f64) {
+    let (roll, pitch) = tilt(accel);
+    let (sin_r, cos_r) = roll.sin_cos();
+    let (sin_p, cos_p) = pitch.sin_cos();
+    // Rotate the reading back to level, undoing the roll and then the pitch.
+    let mx = compass.x * cos_p + compass.y * sin_r * sin_p + compass.z * cos_r * sin_p;
+    let my = compass.y * cos_r - compass.z * sin_r;
+    let yaw = (-my).atan2(mx);
+    (roll, pitch, yaw)
+}
+
+/// Works out `a - b`, wrapped into the range [-180, 180) degrees.
+fn angle_difference(a: Angle, b: Angle) -> Angle {
+    let d = a.as_radians() - b.as_radians();
+    let pi = ::std::f64::consts::PI;
+    Angle::from_radians((d + pi).rem_euclid(2.0 * pi) - pi)
+}
+
+/// Works out (roll, pitch) in radians from the direction of gravity in the
+/// accelerometer reading (in any units).
+fn tilt(accel: Vector3D) -> (f64, f64) {
+    let roll = accel.y.atan2(accel.z);
+    let pitch = (-accel.x).atan2((accel.y * accel.y + accel.z * accel.z).sqrt());
+    (roll, pitch)
+}
+
+/// Are the roll and pitch from the accelerometer reading both within
+/// `tolerance` of zero?
+fn is_level(accel: Vector3D, tolerance: Angle) -> bool {
+    let (roll, pitch) = tilt(accel);
+    let tolerance = tolerance.as_radians().abs();
+    roll.abs() <= tolerance && pitch.abs() <= tolerance
+}
+
+/// Does the acceleration, in Gs, differ from gravity by more than
+/// `threshold_g`?
+fn is_shaking(accel: Vector3D, threshold_g: f64) -> bool {
+    (accel.magnitude() - 1.0).abs() > threshold_g
+}
+
+/// Adds the declination, in degrees, to a magnetic heading, keeping it in
+/// the range [-180, 180).
+fn true_heading(magnetic: Angle, declination: f64) -> Angle {
+    angle_difference(magnetic, Angle::from_degrees(-declination))
+}
+
+/// Converts an angle to a heading in degrees, in the range [0, 360).
+fn heading_degrees(angle: Angle) -> f64 {
+    let degrees = angle.as_degrees().rem_euclid(360.0);
+    // rem_euclid can round up to exactly 360 for tiny negative angles
+    if degrees >= 360.0 {
+        0.0
+    } else {
+        degrees
+    }
+}
+
+/// Formats an optional reading for `log_csv`, as an empty field if it's
+/// missing.
+fn csv_field(value: Option<f64>) -> String {
+    match value {
+        Some(value) => format!("{:.2}", value),
+        None => String::new(),
+    }
+}
+
+/// Expresses a vector in the axes of the LED matrix picture after it has
+/// been turned clockwise by `rotation`. The picture's right is the board's
+/// -y after a quarter turn, for example, and its up is the board's x.
+fn rotate_vector(v: Vector3D, rotation: Rotation) -> Vector3D {
+    let (x, y) = match rotation {
+        Rotation::Deg0 => (v.x, v.y),
+        Rotation::Deg90 => (-v.y, v.x),
+        Rotation::Deg180 => (-v.x, -v.y),
+        Rotation::Deg270 => (v.y, -v.x),
+    };
+    Vector3D { x, y, z: v.z }
+}
+
+/// Turns a `NotReady` error into `None`, passing other errors through.
+fn if_ready<T>(result: SenseHatResult<T>) -> SenseHatResult<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(SenseHatError::NotReady(_)) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Chips on the Sense HAT v2 which answer at the same address as one of the
+/// v1 chips: (v1 chip, WHO_AM_I value, v2 chip).
+const V2_CHIPS: &[(&str, u8, &str)] = &[("LPS25H", lps25h::WHO_AM_I_LPS22HB, "LPS22HB")];
+
+/// Is there an SHTC3 humidity sensor on the given I2C bus? It has no
+/// WHO_AM_I register, but answers a read ID command.
+fn probe_shtc3(path: &str) -> bool {
+    let mut dev = match LinuxI2CDevice::new(path, 0x70) {
+        Ok(dev) => dev,
+        Err(_) => return false,
+    };
+    // Wake it up, then read the ID register
+    if dev.write(&[0x35, 0x17]).is_err() {
+        return false;
+    }
+    ::std::thread::sleep(::std::time::Duration::from_millis(1));
+    let mut id = [0u8; 3];
+    if dev.write(&[0xef, 0xc8]).is_err() || dev.read(&mut id).is_err() {
+        return false;
+    }
+    (u16::from(id[0]) << 8 | u16::from(id[1])) & 0x083f == 0x0807
+}
+
+/// Checks that the chip on the other end of `i2cdev` identifies itself as the
+/// one we expect.
+fn check_who_am_i<T>(
+    i2cdev: &mut T,
+    chip: &'static str,
+    register: u8,
+    expected: u8,
+) -> SenseHatResult<()>
+where
+    T: I2CDevice,
+    SenseHatError: From<T::Error>,
+{
+    let found = i2cdev.smbus_read_byte_data(register)?;
+    if found == expected {
+        return Ok(());
+    }
+    for &(v1_chip, who_am_i, v2_chip) in V2_CHIPS {
+        if v1_chip == chip && who_am_i == found {
+            return Err(SenseHatError::UnsupportedChip(v2_chip));
+        }
+    }
+    Err(SenseHatError::WrongChip(chip, found))
+}
+
+/// How many rows of the matrix `show_bar` lights for `fraction`.
+#[cfg(feature = "led-matrix")]
+fn bar_rows(fraction: f32) -> i32 {
+    if fraction.is_nan() {
+        return 0;
+    }
+    (fraction.clamp(0.0, 1.0) * 8.0).round() as i32
+}
+
+/// Turns a raw RGB565 frame clockwise by `rotation`.
+#[cfg(feature = "led-matrix")]
+fn rotate_frame(bytes: &[u8; 128], rotation: Rotation) -> [u8; 128] {
+    let mut rotated = [0u8; 128];
+    for y in 0..8 {
+        for x in 0..8 {
+            let (rx, ry) = match rotation {
+                Rotation::Deg0 => (x, y),
+                Rotation::Deg90 => (7 - y, x),
+                Rotation::Deg180 => (7 - x, 7 - y),
+                Rotation::Deg270 => (y, 7 - x),
+            };
+            let from = (y * 8 + x) * 2;
+            let to = (ry * 8 + rx) * 2;
+            rotated[to..to + 2].copy_from_slice(&bytes[from..from + 2]);
+        }
+    }
+    rotated
+}
+
+/// Builds the `marquee` frame which starts at column `start`, wrapping
+/// around to the first column after the last.
+#[cfg(feature = "led-matrix")]
+fn marquee_frame(columns: &[[[u8; 2]; 8]], start: usize) -> [u8; 128] {
+    let mut bytes = [0u8; 128];
+    for x in 0..8 {
+        let column = &columns[(start + x) % columns.len()];
+        for (y, pixel) in column.iter().enumerate() {
+            let offset = (y * 8 + x) * 2;
+            bytes[offset..offset + 2].copy_from_slice(pixel);
+        }
+    }
+    bytes
+}
+
+/// Moves the `menu` selection for a joystick push, wrapping around at
+/// either end.
+#[cfg(feature = "led-matrix")]
+fn menu_step(selected: usize, len: usize, direction: Direction) -> usize {
+    match direction {
+        Direction::Up => (selected + len - 1) % len,
+        Direction::Down => (selected + 1) % len,
+        _ => selected,
+    }
+}
+
+/// Turns an error from reading the joystick into a `SenseHatError`, picking
+/// out the device having gone.
+#[cfg(any(feature = "led-matrix", feature = "async"))]
+fn joystick_read_error(err: ::std::io::Error) -> SenseHatError {
+    if err.kind() == ::std::io::ErrorKind::NotConnected {
+        SenseHatError::JoystickDisconnected
+    } else {
+        SenseHatError::JoystickError(err)
+    }
+}
+
+/// Clamps a colour scaling factor to [0, 1], treating NaN as 0, so it can't
+/// make a colour component wrap around.
+#[cfg(feature = "led-matrix")]
+fn unit_factor(factor: f32) -> f32 {
+    if factor.is_nan() {
+        0.0
+    } else {
+        factor.clamp(0.0, 1.0)
+    }
+}
+
+/// Returns each step of scrolling the frames right-to-left. `Scroll` needs
+/// at least two frames, so fewer are returned as they are.
+#[cfg(feature = "led-matrix")]
+fn scroll_steps(pixel_frames: &[PixelFrame]) -> Vec<PixelFrame> {
+    if pixel_frames.len() < 2 {
+        return pixel_frames.to_vec();
+    }
+    sensehat_screen::Scroll::new(pixel_frames)
+        .right_to_left()
+        .collect()
+}
+
+/// Box-filters an RGB image down (or up) to a raw RGB565 frame, for
+/// `show_rgb_buffer`.
+#[cfg(feature = "led-matrix")]
+fn resize_rgb(width: u32, height: u32, data: &[u8]) -> SenseHatResult<[u8; 128]> {
+    let (w, h) = (width as usize, height as usize);
+    if w == 0 || h == 0 || w.checked_mul(h).and_then(|n| n.checked_mul(3)) != Some(data.len()) {
+        return Err(SenseHatError::WrongImageSize(width, height, data.len()));
+    }
+    // The range of source pixels covered by LED `i` of 8, along a side of
+    // `len` pixels. There's always at least one.
+    let span = |i: usize, len: usize| {
+        let start = i * len / 8;
+        start..((i + 1) * len / 8).max(start + 1)
+    };
+    let mut bytes = [0u8; 128];
+    for y in 0..8 {
+        for x in 0..8 {
+            let mut sum = [0u32; 3];
+            let mut count = 0;
+            for sy in span(y, h) {
+                for sx in span(x, w) {
+                    let offset = (sy * w + sx) * 3;
+                    for (total, &c) in sum.iter_mut().zip(&data[offset..offset + 3]) {
+                        *total += u32::from(c);
+                    }
+                    count += 1;
+                }
+            }
+            let average = |total: u32| ((total + count / 2) / count) as u8;
+            let colour: Colour = (average(sum[0]), average(sum[1]), average(sum[2])).into();
+            put_pixel(&mut bytes, x as i32, y as i32, colour);
+        }
+    }
+    Ok(bytes)
+}
+
+/// Renders a raw RGB565 frame for `dump_frame_ascii`.
+#[cfg(feature = "led-matrix")]
+fn frame_to_string(bytes: &[u8; 128], ansi: bool) -> String {
+    let mut out = String::new();
+    for row in bytes.chunks(16) {
+        for pixel in row.chunks(2) {
+            let raw = u16::from(pixel[0]) | (u16::from(pixel[1]) << 8);
+            if ansi {
+                let c = Colour::from_rgb565(raw).0;
+                out.push_str(&format!(
+                    "\x1b[38;2;{};{};{}m\u{2588}\u{2588}",
+                    c.red, c.green, c.blue
+                ));
+            } else if raw == 0 {
+                out.push('.');
+            } else {
+                out.push('#');
+            }
+        }
+        if ansi {
+            out.push_str("\x1b[0m");
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Picks the colour for `show_threshold`.
+#[cfg(feature = "led-matrix")]
+fn threshold_colour(value: f64, low: f64, high: f64) -> Colour {
+    let margin = (high - low).abs() / 10.0;
+    if (low + margin..=high - margin).contains(&value) {
+        Colour::GREEN
+    } else if (low - margin..=high + margin).contains(&value) {
+        Colour::AMBER
+    } else {
+        Colour::RED
+    }
+}
+
+/// Sets one pixel in a raw RGB565 frame, ignoring pixels outside the matrix.
+#[cfg(feature = "led-matrix")]
+fn put_pixel(bytes: &mut [u8; 128], x: i32, y: i32, colour: Colour) {
+    if (0..8).contains(&x) && (0..8).contains(&y) {
+        let offset = ((y * 8 + x) * 2) as usize;
+        bytes[offset..offset + 2].copy_from_slice(&colour.0.rgb565());
+    }
+}
+
+/// Draws a line in a raw RGB565 frame using Bresenham's algorithm, after
+/// clipping it to the matrix.
+#[cfg(feature = "led-matrix")]
+fn put_line(bytes: &mut [u8; 128], x0: i32, y0: i32, x1: i32, y1: i32, colour: Colour) {
+    let (x0, y0, x1, y1) = match clip_line(x0, y0, x1, y1) {
+        Some(line) => line,
+        None => return,
+    };
+    let dx = (x1 - x0).abs();
+    let dy = -(y1 - y0).abs();
+    let sx = if x0 < x1 { 1 } else { -1 };
+    let sy = if y0 < y1 { 1 } else { -1 };
+    let mut err = dx + dy;
+    let (mut x, mut y) = (x0, y0);
+    loop {
+        put_pixel(bytes, x, y, colour);
+        if x == x1 && y == y1 {
+            break;
+        }
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            y += sy;
+        }
+    }
+}
+
+/// Clips a line to the part that crosses the matrix, with the Liang–Barsky
+/// algorithm, so that it can be drawn without overflowing or visiting
+/// pixels that are off the matrix. Each pixel is taken to be the square
+/// around its centre. Returns `None` if the line misses the matrix.
+#[cfg(feature = "led-matrix")]
+fn clip_line(x0: i32, y0: i32, x1: i32, y1: i32) -> Option<(i32, i32, i32, i32)> {
+    let (min, max) = (-0.5, 7.5);
+    // Differences of two i32s fit exactly in an i64, and in an f64.
+    let dx = (i64::from(x1) - i64::from(x0)) as f64;
+    let dy = (i64::from(y1) - i64::from(y0)) as f64;
+    let (x0, y0) = (f64::from(x0), f64::from(y0));
+    let (mut t0, mut t1) = (0.0f64, 1.0f64);
+    for &(p, q) in &[
+        (-dx, x0 - min),
+        (dx, max - x0),
+        (-dy, y0 - min),
+        (dy, max - y0),
+    ] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else {
+            let r = q / p;
+            if p < 0.0 {
+                t0 = t0.max(r);
+            } else {
+                t1 = t1.min(r);
+            }
+        }
+    }
+    if t0 > t1 {
+        return None;
+    }
+    let pixel = |v: f64| v.round().clamp(0.0, 7.0) as i32;
+    Some((
+        pixel(x0 + t0 * dx),
+        pixel(y0 + t0 * dy),
+        pixel(x0 + t1 * dx),
+        pixel(y0 + t1 * dy),
+    ))
+}
+
+/// Draws a `w` by `h` rectangle in a raw RGB565 frame, clipped to the
+/// matrix.
+#[cfg(feature = "led-matrix")]
+fn put_rect(bytes: &mut [u8; 128], x: i32, y: i32, w: i32, h: i32, colour: Colour, filled: bool) {
+    if w <= 0 || h <= 0 {
+        return;
+    }
+    let (left, top) = (i64::from(x), i64::from(y));
+    let (right, bottom) = (left + i64::from(w) - 1, top + i64::from(h) - 1);
+    if right < 0 || bottom < 0 || left > 7 || top > 7 {
+        return;
+    }
+    // Everything from here on is on the matrix, so fits in an i32.
+    let clip = |v: i64| v.clamp(0, 7) as i32;
+    let (x0, x1, y0, y1) = (clip(left), clip(right), clip(top), clip(bottom));
+    if filled {
+        for row in y0..=y1 {
+            put_line(bytes, x0, row, x1, row, colour);
+        }
+        return;
+    }
+    if top >= 0 {
+        put_line(bytes, x0, y0, x1, y0, colour);
+    }
+    if bottom <= 7 {
+        put_line(bytes, x0, y1, x1, y1, colour);
+    }
+    if left >= 0 {
+        put_line(bytes, x0, y0, x0, y1, colour);
+    }
+    if right <= 7 {
+        put_line(bytes, x1, y0, x1, y1, colour);
     }
 }
 
@@ -367,6 +3262,12 @@ impl From<LinuxI2CError> for SenseHatError {
     }
 }
 
+impl From<::std::io::Error> for SenseHatError {
+    fn from(err: ::std::io::Error) -> SenseHatError {
+        SenseHatError::Io(err)
+    }
+}
+
 impl From<lsm9ds1::Error> for SenseHatError {
     fn from(err: lsm9ds1::Error) -> SenseHatError {
         SenseHatError::LSM9DS1Error(err)
@@ -382,7 +3283,17 @@ impl From<std::string::FromUtf16Error> for SenseHatError {
 #[cfg(feature = "led-matrix")]
 impl From<sensehat_screen::error::ScreenError> for SenseHatError {
     fn from(err: sensehat_screen::error::ScreenError) -> SenseHatError {
-        SenseHatError::ScreenError(err)
+        match err {
+            sensehat_screen::error::ScreenError::Unicode(err) => SenseHatError::CharacterError(err),
+            err => SenseHatError::ScreenError(err),
+        }
+    }
+}
+
+#[cfg(feature = "led-matrix")]
+impl From<sensehat_screen::framebuffer::FramebufferError> for SenseHatError {
+    fn from(err: sensehat_screen::framebuffer::FramebufferError) -> SenseHatError {
+        SenseHatError::ScreenError(err.into())
     }
 }
 
@@ -401,6 +3312,13 @@ impl<'a> Into<Colour> for (u8, u8, u8) {
     }
 }
 
+#[cfg(feature = "led-matrix")]
+impl From<Colour> for (u8, u8, u8) {
+    fn from(colour: Colour) -> (u8, u8, u8) {
+        colour.rgb()
+    }
+}
+
 #[cfg(feature = "led-matrix")]
 impl<'a> Into<::std::time::Duration> for Fps {
     fn into(self) -> ::std::time::Duration {
@@ -418,11 +3336,570 @@ impl Colour {
     pub const YELLOW: Colour = Colour(PixelColor::YELLOW);
     pub const MAGENTA: Colour = Colour(PixelColor::MAGENTA);
     pub const CYAN: Colour = Colour(PixelColor::CYAN);
+    pub const AMBER: Colour = Colour(PixelColor {
+        red: 0xff,
+        green: 0xbf,
+        blue: 0x00,
+    });
+
+    /// Creates a colour from its RGB565 encoding, as used by the LED matrix
+    /// framebuffer: 5 bits of red, 6 of green, then 5 of blue. Each
+    /// component's missing low bits are zero, so `to_rgb565` gives the same
+    /// value back.
+    pub fn from_rgb565(raw: u16) -> Colour {
+        Colour(sensehat_screen::color::Rgb565::from(raw).into())
+    }
+
+    /// Returns the red, green and blue components, from 0 to 255.
+    ///
+    /// These are exactly the values the colour was made with. The LED
+    /// matrix only has 5 bits of red and blue and 6 of green, though, so a
+    /// colour read back from it (with `from_rgb565`) has the low bits of
+    /// each component cleared: `(0x12, 0x34, 0x56)` comes back as
+    /// `(0x10, 0x34, 0x50)`.
+    pub fn rgb(self) -> (u8, u8, u8) {
+        (self.0.red, self.0.green, self.0.blue)
+    }
+
+    /// Returns the colour in RGB565 encoding, dropping the low bits of each
+    /// component.
+    pub fn to_rgb565(self) -> u16 {
+        sensehat_screen::color::Rgb565::from(self.0).into()
+    }
+
+    /// Returns the colour at `factor` brightness, from 0.0 (black) to 1.0
+    /// (unchanged). Factors outside that range are clamped, and NaN counts
+    /// as 0.
+    pub fn dim(self, factor: f32) -> Colour {
+        Colour(self.0.dim(unit_factor(factor)))
+    }
+
+    /// Mixes two colours, going in a straight line from `self` (when `t` is
+    /// 0.0) to `other` (when `t` is 1.0). `t` is clamped to that range, and
+    /// NaN counts as 0.
+    pub fn blend(self, other: Colour, t: f32) -> Colour {
+        let t = unit_factor(t);
+        let mix = |a: u8, b: u8| (f32::from(a) + (f32::from(b) - f32::from(a)) * t).round() as u8;
+        Colour(PixelColor::new(
+            mix(self.0.red, other.0.red),
+            mix(self.0.green, other.0.green),
+            mix(self.0.blue, other.0.blue),
+        ))
+    }
+
+    /// Creates a colour from its hue, in degrees around the colour wheel
+    /// (0 is red, 120 green and 240 blue), and its saturation and value,
+    /// from 0.0 to 1.0. Hues outside 0 to 360 wrap around; saturations and
+    /// values outside 0 to 1 are clamped, and NaN counts as 0.
+    pub fn from_hsv(hue: f32, saturation: f32, value: f32) -> Colour {
+        let (s, v) = (unit_factor(saturation), unit_factor(value));
+        let h = if hue.is_finite() {
+            hue.rem_euclid(360.0) / 60.0
+        } else {
+            0.0
+        };
+        let c = v * s;
+        let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+        let (r, g, b) = match h as u32 {
+            0 => (c, x, 0.0),
+            1 => (x, c, 0.0),
+            2 => (0.0, c, x),
+            3 => (0.0, x, c),
+            4 => (x, 0.0, c),
+            _ => (c, 0.0, x),
+        };
+        let m = v - c;
+        let byte = |f: f32| ((f + m) * 255.0).round() as u8;
+        Colour(PixelColor::new(byte(r), byte(g), byte(b)))
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use mock::MockI2CDevice;
+
+    // With the `rtimu` feature the IMU needs real hardware, so the mock
+    // tests only run without it.
+    #[cfg(not(feature = "rtimu"))]
+    fn mock_hat<'a>() -> SenseHat<'a, MockI2CDevice> {
+        SenseHat::with_devices(MockI2CDevice::hts221(), MockI2CDevice::lps25h()).unwrap()
+    }
+
+    /// A writer which takes `limit` bytes, then fails.
+    #[cfg(not(feature = "rtimu"))]
+    struct FailingWriter {
+        limit: usize,
+    }
+
+    #[cfg(not(feature = "rtimu"))]
+    impl ::std::io::Write for FailingWriter {
+        fn write(&mut self, buf: &[u8]) -> ::std::io::Result<usize> {
+            if self.limit == 0 {
+                return Err(::std::io::Error::new(
+                    ::std::io::ErrorKind::WriteZero,
+                    "disk full",
+                ));
+            }
+            let len = buf.len().min(self.limit);
+            self.limit -= len;
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> ::std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[cfg(not(feature = "rtimu"))]
+    #[test]
+    fn check_log_csv_writer_error() {
+        let mut hat = mock_hat();
+        let interval = ::std::time::Duration::from_millis(0);
+        let mut output = Vec::new();
+        hat.log_csv(&mut output, interval, 2).unwrap();
+        let text = String::from_utf8(output).unwrap();
+        assert_eq!(text.lines().count(), 3);
+        assert!(text.starts_with("timestamp,temperature,pressure,humidity\n"));
+        match hat.log_csv(FailingWriter { limit: 50 }, interval, 2) {
+            Err(SenseHatError::Io(ref err)) if err.kind() == ::std::io::ErrorKind::WriteZero => {}
+            other => panic!("expected an Io error, got {:?}", other),
+        }
+    }
+
+    #[cfg(not(feature = "rtimu"))]
+    #[test]
+    fn check_stream_orientation_writer_error() {
+        let mut hat = mock_hat();
+        let interval = ::std::time::Duration::from_millis(0);
+        match hat.stream_orientation(FailingWriter { limit: 40 }, interval, 2) {
+            Err(SenseHatError::Io(ref err)) if err.kind() == ::std::io::ErrorKind::WriteZero => {}
+            other => panic!("expected an Io error, got {:?}", other),
+        }
+    }
+
+    #[cfg(not(feature = "rtimu"))]
+    #[test]
+    fn check_mock_humidity_reading() {
+        let mut hat = mock_hat();
+        hat.set_temperature_offset(Temperature::from_celsius(1.0));
+        let (temp, rh) = hat.get_humidity_reading().unwrap();
+        assert!((temp.as_celsius() - 24.0).abs() < 1e-9);
+        assert!((rh.as_percent() - 50.0).abs() < 1e-9);
+        // Only the temperature is ready
+        let mut hts221_dev = MockI2CDevice::hts221();
+        hts221_dev.set_register(hts221::REG_STATUS, 0x01);
+        let mut hat = SenseHat::with_devices(hts221_dev, MockI2CDevice::lps25h()).unwrap();
+        match hat.get_humidity_reading() {
+            Err(SenseHatError::NotReady(Subsystem::Humidity)) => {}
+            other => panic!("expected NotReady, got {:?}", other),
+        }
+    }
+
+    #[cfg(not(feature = "rtimu"))]
+    #[test]
+    fn check_mock_reading() {
+        let mut hat = mock_hat();
+        let reading = hat.get_reading().unwrap();
+        assert!(reading.environment.pressure.is_some());
+        assert!(reading.imu.is_none());
+    }
+
+    #[cfg(not(feature = "rtimu"))]
+    #[test]
+    fn check_mock_log_csv() {
+        let mut hat = mock_hat();
+        hat.pressure_chip.power_down().unwrap();
+        let mut csv = Vec::new();
+        hat.log_csv(&mut csv, ::std::time::Duration::from_millis(0), 2)
+            .unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "timestamp,temperature,pressure,humidity");
+        assert!(lines[1].ends_with(",25.00,,50.00"));
+    }
+
+    #[cfg(not(feature = "rtimu"))]
+    #[test]
+    fn check_mock_stream_orientation() {
+        // The mock has no IMU, so every row is blank
+        let mut hat = mock_hat();
+        let mut csv = Vec::new();
+        hat.stream_orientation(&mut csv, ::std::time::Duration::from_millis(1), 3)
+            .unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], "timestamp,roll,pitch,yaw,heading");
+        assert!(lines[1..].iter().all(|line| line.ends_with(",,,,")));
+    }
+
+    #[cfg(not(feature = "rtimu"))]
+    #[test]
+    fn check_mock_self_test() {
+        let mut hat = mock_hat();
+        assert!(hat.self_test().unwrap().passed());
+        hat.pressure_chip.power_down().unwrap();
+        let report = hat.self_test().unwrap();
+        assert_eq!(report.humidity, SelfTestResult::Passed);
+        assert_eq!(report.pressure, SelfTestResult::NoReading);
+    }
+
+    #[cfg(not(feature = "rtimu"))]
+    #[test]
+    fn check_mock_temperature_units() {
+        let mut hat = mock_hat();
+        assert!((hat.get_temperature_fahrenheit().unwrap() - 77.0).abs() < 0.1);
+        assert!((hat.get_temperature_kelvin().unwrap() - 298.15).abs() < 0.1);
+        assert!((hat.get_temperature_from_humidity_fahrenheit().unwrap() - 77.0).abs() < 0.1);
+        assert!((hat.get_temperature_from_humidity_kelvin().unwrap() - 298.15).abs() < 0.1);
+        assert!((hat.get_temperature_from_pressure_fahrenheit().unwrap() - 77.0).abs() < 0.1);
+        assert!((hat.get_temperature_from_pressure_kelvin().unwrap() - 298.15).abs() < 0.1);
+    }
+
+    #[cfg(not(feature = "rtimu"))]
+    #[test]
+    fn check_mock_two_hats() {
+        let mut first = mock_hat();
+        let mut lps25h = MockI2CDevice::lps25h();
+        // 1000 hPa
+        lps25h.set_registers(lps25h::REG_PRESS_OUT_XL, &[0x00, 0x80, 0x3e]);
+        let mut second = SenseHat::with_devices(MockI2CDevice::hts221(), lps25h).unwrap();
+        second.set_temperature_offset(Temperature::from_celsius(5.0));
+        let p1 = first.get_pressure().unwrap().as_hectopascals();
+        let p2 = second.get_pressure().unwrap().as_hectopascals();
+        assert!((p1 - 1013.25).abs() < 0.01);
+        assert!((p2 - 1000.0).abs() < 0.01);
+        let t1 = first.get_temperature().unwrap().as_celsius();
+        let t2 = second.get_temperature().unwrap().as_celsius();
+        assert!((t1 - 25.0).abs() < 0.1);
+        assert!((t2 - 20.0).abs() < 0.1);
+    }
+
+    #[cfg(not(feature = "rtimu"))]
+    #[test]
+    fn check_mock_pressure_trend() {
+        let mut hat = mock_hat();
+        let window = ::std::time::Duration::from_secs(3600);
+        assert_eq!(hat.pressure_trend(window).unwrap().len(), 1);
+        ::std::thread::sleep(::std::time::Duration::from_millis(2));
+        let trend = hat.pressure_trend(window).unwrap();
+        assert_eq!(trend.len(), 2);
+        assert_eq!(trend.tendency(), Some(Tendency::Steady));
+    }
+
+    #[cfg(not(feature = "rtimu"))]
+    #[test]
+    fn check_mock_timestamped() {
+        let mut hat = mock_hat();
+        let before = ::std::time::Instant::now();
+        let (rh, humidity_time) = hat.get_humidity_timestamped().unwrap();
+        let (temp, temperature_time) = hat.get_temperature_timestamped().unwrap();
+        let (pressure, pressure_time) = hat.get_pressure_timestamped().unwrap();
+        assert!((rh.as_percent() - 50.0).abs() < 0.1);
+        assert!((temp.as_celsius() - 25.0).abs() < 0.1);
+        assert!((pressure.as_hectopascals() - 1013.25).abs() < 0.1);
+        assert!(before <= humidity_time);
+        assert!(humidity_time <= temperature_time);
+        assert!(temperature_time <= pressure_time);
+        assert!(pressure_time <= ::std::time::Instant::now());
+    }
+
+    #[cfg(all(feature = "led-matrix", not(feature = "rtimu")))]
+    #[test]
+    fn check_render_message() {
+        let hat = mock_hat();
+        let frames = hat
+            .render_message("Hi!", Colour::WHITE, Colour::BLACK)
+            .unwrap();
+        assert_eq!(frames.len(), 3);
+    }
+
+    #[cfg(all(feature = "led-matrix", not(feature = "rtimu")))]
+    #[test]
+    fn check_supported_characters() {
+        let mut hat = mock_hat();
+        let chars = hat.supported_characters();
+        assert!(chars.contains(&'A'));
+        assert!(chars.contains(&'é'));
+        assert!(chars.contains(&'┼'));
+        assert!(chars.contains(&'あ'));
+        assert!(!chars.contains(&'€'));
+        assert!(chars.windows(2).all(|pair| pair[0] < pair[1]));
+        let mut fonts = ::std::collections::HashMap::new();
+        fonts.insert('x', FontUnicode('x', [0; 8]));
+        hat.set_font(FontCollection::from_hashmap(fonts));
+        assert_eq!(hat.supported_characters(), vec!['x']);
+    }
+
+    /// An IMU which never has data, and counts how often its mode changes.
+    struct ModeCounter(::std::rc::Rc<::std::cell::Cell<usize>>);
+
+    impl ImuBackend for ModeCounter {
+        fn imu_read(&mut self) -> bool {
+            false
+        }
+
+        fn poll_interval(&self) -> ::std::time::Duration {
+            ::std::time::Duration::from_millis(4)
+        }
+
+        fn get_imu_data(&mut self) -> SenseHatResult<ImuData> {
+            Err(SenseHatError::NotReady(Subsystem::Imu))
+        }
+
+        fn set_fusion(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+
+        fn set_compass_only(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+
+        fn set_gyro_only(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+
+        fn set_accel_only(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn check_imu_mode_cached() {
+        let changes = ::std::rc::Rc::new(::std::cell::Cell::new(0));
+        let mut hat = SenseHat::from_devices(
+            MockI2CDevice::hts221(),
+            MockI2CDevice::lps25h(),
+            Box::new(ModeCounter(changes.clone())),
+        )
+        .unwrap();
+        assert!(hat.get_orientation().is_err());
+        assert!(hat.get_accel_raw().is_err());
+        assert!(hat.get_gyro_raw().is_err());
+        assert!(hat.get_compass_raw().is_err());
+        assert!(hat.read_imu().is_err());
+        assert_eq!(changes.get(), 1);
+        assert!(hat.get_compass().is_err());
+        assert!(hat.get_compass().is_err());
+        assert!(hat.get_orientation().is_err());
+        assert_eq!(changes.get(), 3);
+    }
+
+    #[test]
+    fn check_orientation_timeout() {
+        let changes = ::std::rc::Rc::new(::std::cell::Cell::new(0));
+        let mut hat = SenseHat::from_devices(
+            MockI2CDevice::hts221(),
+            MockI2CDevice::lps25h(),
+            Box::new(ModeCounter(changes)),
+        )
+        .unwrap();
+        let timeout = ::std::time::Duration::from_millis(20);
+        let start = ::std::time::Instant::now();
+        match hat.get_orientation_timeout(timeout) {
+            Err(SenseHatError::Timeout(Subsystem::Imu, t)) => assert_eq!(t, timeout),
+            other => panic!("expected a timeout, got {:?}", other),
+        }
+        assert!(start.elapsed() >= timeout);
+    }
+
+    #[cfg(not(feature = "rtimu"))]
+    #[test]
+    fn check_mock_environment_stream() {
+        let mut hat = mock_hat();
+        let interval = ::std::time::Duration::from_millis(5);
+        let start = ::std::time::Instant::now();
+        let readings: Vec<_> = hat.environment_stream(interval).take(3).collect();
+        assert!(start.elapsed() >= interval * 2);
+        assert_eq!(readings.len(), 3);
+        assert!(readings.iter().all(|r| r.is_ok()));
+    }
+
+    #[cfg(not(feature = "rtimu"))]
+    #[test]
+    fn check_mock_readings() {
+        let mut hat = mock_hat();
+        let temp = hat.get_temperature_from_humidity().unwrap();
+        assert!((temp.as_celsius() - 25.0).abs() < 1e-9);
+        let rh = hat.get_humidity().unwrap();
+        assert!((rh.as_percent() - 50.0).abs() < 1e-9);
+        let temp = hat.get_temperature_from_pressure().unwrap();
+        assert!((temp.as_celsius() - 25.0).abs() < 1e-9);
+        let pressure = hat.get_pressure().unwrap();
+        assert!((pressure.as_hectopascals() - 1013.25).abs() < 1e-9);
+        assert!((hat.get_pressure_pa().unwrap() - 101_325.0).abs() < 1e-6);
+        assert!((hat.get_pressure_mbar().unwrap() - 1013.25).abs() < 1e-9);
+    }
+
+    #[cfg(not(feature = "rtimu"))]
+    #[test]
+    fn check_mock_temperature_fallback() {
+        let mut hts221_dev = MockI2CDevice::hts221();
+        // Humidity ready, temperature not
+        hts221_dev.set_register(hts221::REG_STATUS, 0x02);
+        let mut lps25h_dev = MockI2CDevice::lps25h();
+        // (30 - 42.5) * 480 = -6000
+        lps25h_dev.set_registers(lps25h::REG_TEMP_OUT_L, &[0x90, 0xe8]);
+        let mut hat = SenseHat::with_devices(hts221_dev, lps25h_dev).unwrap();
+        let temp = hat.get_temperature().unwrap();
+        assert!((temp.as_celsius() - 30.0).abs() < 1e-9);
+        let temp = hat.get_temperature_averaged().unwrap();
+        assert!((temp.as_celsius() - 30.0).abs() < 1e-9);
+    }
+
+    #[cfg(not(feature = "rtimu"))]
+    #[test]
+    fn check_mock_temperature_averaged() {
+        let mut lps25h_dev = MockI2CDevice::lps25h();
+        // (42.5 - 42.5) * 480 = 0
+        lps25h_dev.set_registers(lps25h::REG_TEMP_OUT_L, &[0, 0]);
+        let mut hat = SenseHat::with_devices(MockI2CDevice::hts221(), lps25h_dev).unwrap();
+        // 25 and 42.5, weighted 16:1
+        let temp = hat.get_temperature_averaged().unwrap();
+        assert!((temp.as_celsius() - 26.029_411_764_7).abs() < 1e-9);
+    }
+
+    #[cfg(not(feature = "rtimu"))]
+    #[test]
+    fn check_mock_blocking_timeout() {
+        let mut hts221_dev = MockI2CDevice::hts221();
+        hts221_dev.set_register(hts221::REG_STATUS, 0x00);
+        let mut hat = SenseHat::with_devices(hts221_dev, MockI2CDevice::lps25h()).unwrap();
+        let timeout = ::std::time::Duration::from_millis(20);
+        let start = ::std::time::Instant::now();
+        match hat.get_humidity_blocking(timeout) {
+            Err(SenseHatError::NotReady(Subsystem::Humidity)) => {}
+            other => panic!("expected NotReady, got {:?}", other),
+        }
+        assert!(start.elapsed() >= timeout);
+        assert!(hat.get_pressure_blocking(timeout).is_ok());
+    }
+
+    #[cfg(not(feature = "rtimu"))]
+    #[test]
+    fn check_mock_prime() {
+        let mut hat = mock_hat();
+        assert!(hat.prime(2).is_ok());
+        // A powered-down sensor is skipped rather than waited for
+        hat.pressure_chip.power_down().unwrap();
+        let start = ::std::time::Instant::now();
+        assert!(hat.prime(2).is_ok());
+        assert!(start.elapsed() < ::std::time::Duration::from_secs(1));
+    }
+
+    #[cfg(not(feature = "rtimu"))]
+    #[test]
+    fn check_environment() {
+        let env = mock_hat().get_environment().unwrap();
+        assert!((env.temperature_from_humidity.unwrap().as_celsius() - 25.0).abs() < 1e-9);
+        assert!((env.temperature_from_pressure.unwrap().as_celsius() - 25.0).abs() < 1e-9);
+        assert!((env.pressure.unwrap().as_hectopascals() - 1013.25).abs() < 1e-9);
+        assert!((env.humidity.unwrap().as_percent() - 50.0).abs() < 1e-9);
+        // Only the humidity is ready
+        let mut hts221_dev = MockI2CDevice::hts221();
+        hts221_dev.set_register(hts221::REG_STATUS, 0x02);
+        let mut hat = SenseHat::with_devices(hts221_dev, MockI2CDevice::lps25h()).unwrap();
+        let env = hat.get_environment().unwrap();
+        assert!(env.temperature_from_humidity.is_none());
+        assert!((env.humidity.unwrap().as_percent() - 50.0).abs() < 1e-9);
+    }
+
+    #[cfg(not(feature = "rtimu"))]
+    #[test]
+    fn check_mock_power_down() {
+        let mut hat = mock_hat();
+        hat.power_down().unwrap();
+        match hat.get_humidity() {
+            Err(SenseHatError::NotReady(Subsystem::Humidity)) => {}
+            other => panic!("expected NotReady, got {:?}", other),
+        }
+        let env = hat.get_environment().unwrap();
+        assert!(env.humidity.is_none());
+        assert!(env.pressure.is_none());
+        hat.power_up().unwrap();
+        assert!(hat.get_humidity().is_ok());
+    }
+
+    #[cfg(all(feature = "led-matrix", not(feature = "rtimu")))]
+    #[test]
+    fn check_missing_framebuffer() {
+        let mut hat = mock_hat();
+        hat.set_framebuffer_path("/dev/does-not-exist");
+        match hat.clear() {
+            Err(SenseHatError::FramebufferError(ref path, ref err))
+                if path == "/dev/does-not-exist"
+                    && err.kind() == ::std::io::ErrorKind::NotFound => {}
+            other => panic!("expected FramebufferError, got {:?}", other),
+        }
+    }
+
+    #[cfg(all(feature = "led-matrix", not(feature = "rtimu")))]
+    #[test]
+    fn check_text_frames() {
+        let mut hat = mock_hat();
+        let frames = hat.text_frames("Hi", Colour::WHITE, Colour::BLACK).unwrap();
+        let rendered = hat
+            .render_message("Hi", Colour::WHITE, Colour::BLACK)
+            .unwrap();
+        // Eight steps to move each character one place along, and then the
+        // last one on its own
+        assert_eq!(frames.len(), 8 * (rendered.len() - 1) + 1);
+        assert_eq!(frames[0], rendered[0]);
+        assert_eq!(
+            hat.text_frames("H", Colour::WHITE, Colour::BLACK)
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(hat
+            .text_frames("", Colour::WHITE, Colour::BLACK)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[cfg(all(feature = "led-matrix", not(feature = "rtimu")))]
+    #[test]
+    fn check_back_buffer() {
+        let mut hat = mock_hat();
+        hat.set_framebuffer_path("/dev/does-not-exist");
+        // Nothing to show, so the framebuffer isn't needed
+        assert!(hat.present().is_ok());
+        assert!(hat.begin_frame().is_err());
+        assert!(hat.back_buffer.is_none());
+        // Drawing into the back buffer doesn't touch the framebuffer either
+        hat.back_buffer = Some([0; 128]);
+        hat.set_pixel(1, 0, Colour::RED).unwrap();
+        hat.draw_line(0, 7, 7, 7, Colour::WHITE).unwrap();
+        let plain = frame_to_string(&hat.frame_bytes().unwrap(), false);
+        assert_eq!(plain.lines().next(), Some(".#......"));
+        assert_eq!(plain.lines().nth(7), Some("########"));
+        assert!(hat.present().is_err());
+        assert!(hat.back_buffer.is_none());
+    }
+
+    #[test]
+    fn check_mock_wrong_chip() {
+        let result = SenseHat::with_devices(MockI2CDevice::new(), MockI2CDevice::lps25h());
+        match result {
+            Err(SenseHatError::WrongChip("HTS221", 0)) => {}
+            Err(e) => panic!("expected WrongChip, got {:?}", e),
+            Ok(_) => panic!("expected WrongChip"),
+        }
+    }
+
+    #[test]
+    fn check_mock_v2() {
+        let mut lps22hb = MockI2CDevice::lps25h();
+        lps22hb.set_register(lps25h::REG_WHO_AM_I, 0xb1);
+        let result = SenseHat::with_devices(MockI2CDevice::hts221(), lps22hb);
+        match result {
+            Err(SenseHatError::UnsupportedChip("LPS22HB")) => {}
+            Err(e) => panic!("expected UnsupportedChip, got {:?}", e),
+            Ok(_) => panic!("expected UnsupportedChip"),
+        }
+    }
 
     #[cfg(feature = "led-matrix")]
     #[test]
@@ -439,6 +3916,506 @@ mod test {
         let colour_const: Colour = Colour::RED;
         assert_eq!(colour_tuple, colour_const);
     }
+
+    #[cfg(feature = "led-matrix")]
+    #[test]
+    fn check_colours_rgb565() {
+        let colour: Colour = (0xf8, 0, 0).into();
+        assert_eq!(Colour::from_rgb565(0xf800), colour);
+        assert_eq!(Colour::RED.to_rgb565(), 0xf800);
+        for raw in 0..=0xffff {
+            assert_eq!(Colour::from_rgb565(raw).to_rgb565(), raw);
+        }
+    }
+
+    #[cfg(feature = "led-matrix")]
+    #[test]
+    fn check_colours_dim_blend() {
+        assert_eq!(Colour::WHITE.dim(0.0), Colour::BLACK);
+        assert_eq!(Colour::WHITE.dim(1.0), Colour::WHITE);
+        assert_eq!(Colour::RED.blend(Colour::BLUE, 0.0), Colour::RED);
+        assert_eq!(Colour::RED.blend(Colour::BLUE, 1.0), Colour::BLUE);
+        let purple: Colour = (0x80, 0, 0x80).into();
+        assert_eq!(Colour::RED.blend(Colour::BLUE, 0.5), purple);
+        assert_eq!(Colour::RED.blend(Colour::BLUE, 2.0), Colour::BLUE);
+    }
+
+    #[cfg(feature = "led-matrix")]
+    #[test]
+    fn check_joystick_read_error() {
+        let gone = ::std::io::Error::new(::std::io::ErrorKind::NotConnected, "gone");
+        match joystick_read_error(gone) {
+            SenseHatError::JoystickDisconnected => {}
+            other => panic!("expected JoystickDisconnected, got {:?}", other),
+        }
+        match joystick_read_error(::std::io::Error::from_raw_os_error(5)) {
+            SenseHatError::JoystickError(_) => {}
+            other => panic!("expected JoystickError, got {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "led-matrix")]
+    #[test]
+    fn check_colours_bad_factors() {
+        let colour: Colour = (0x12, 0x34, 0x56).into();
+        for &factor in &[f32::NAN, -1.0, f32::NEG_INFINITY] {
+            assert_eq!(colour.dim(factor), Colour::BLACK);
+            assert_eq!(colour.blend(Colour::WHITE, factor), colour);
+            assert_eq!(Colour::from_hsv(0.0, 1.0, factor), Colour::BLACK);
+        }
+        for &factor in &[2.0, f32::INFINITY] {
+            assert_eq!(colour.dim(factor), colour);
+            assert_eq!(colour.blend(Colour::WHITE, factor), Colour::WHITE);
+            assert_eq!(Colour::from_hsv(0.0, factor, factor), Colour::RED);
+        }
+    }
+
+    #[cfg(feature = "led-matrix")]
+    #[test]
+    fn check_rotate_frame() {
+        let mut bytes = [0u8; 128];
+        put_pixel(&mut bytes, 1, 0, Colour::RED);
+        let rotated = rotate_frame(&bytes, Rotation::Deg90);
+        let plain = frame_to_string(&rotated, false);
+        assert_eq!(plain.lines().nth(1), Some(".......#"));
+        for &rotation in &[
+            Rotation::Deg0,
+            Rotation::Deg90,
+            Rotation::Deg180,
+            Rotation::Deg270,
+        ] {
+            let turned = rotate_frame(&bytes, rotation);
+            assert_eq!(rotate_frame(&turned, rotation.inverse())[..], bytes[..]);
+        }
+    }
+
+    #[test]
+    fn check_rotate_vector() {
+        let down = Vector3D {
+            x: 0.0,
+            y: -1.0,
+            z: 0.0,
+        };
+        // Turning the picture a quarter clockwise makes the board's -y
+        // point to the picture's right, so gravity along the board's -x is
+        // down in the picture.
+        let gravity = Vector3D {
+            x: -1.0,
+            y: 0.0,
+            z: 0.0,
+        };
+        let turned = rotate_vector(gravity, Rotation::Deg90);
+        assert!((turned.x - down.x).abs() < 1e-9 && (turned.y - down.y).abs() < 1e-9);
+        let turned = rotate_vector(down, Rotation::Deg180);
+        assert!((turned.y - 1.0).abs() < 1e-9);
+    }
+
+    #[cfg(feature = "led-matrix")]
+    #[test]
+    fn check_marquee_frame() {
+        let on = [Colour::RED.0.rgb565(); 8];
+        let off = [[0u8; 2]; 8];
+        let columns = vec![on, off, off];
+        let plain = frame_to_string(&marquee_frame(&columns, 0), false);
+        assert_eq!(plain.lines().next(), Some("#..#..#."));
+        let plain = frame_to_string(&marquee_frame(&columns, 2), false);
+        assert_eq!(plain.lines().next(), Some(".#..#..#"));
+    }
+
+    #[cfg(feature = "led-matrix")]
+    #[test]
+    fn check_menu_step() {
+        assert_eq!(menu_step(0, 3, Direction::Down), 1);
+        assert_eq!(menu_step(2, 3, Direction::Down), 0);
+        assert_eq!(menu_step(0, 3, Direction::Up), 2);
+        assert_eq!(menu_step(1, 3, Direction::Left), 1);
+        assert_eq!(menu_step(0, 1, Direction::Up), 0);
+    }
+
+    #[cfg(feature = "led-matrix")]
+    #[test]
+    fn check_frame_to_string() {
+        let mut bytes = [0u8; 128];
+        put_line(&mut bytes, 0, 0, 7, 7, Colour::RED);
+        let plain = frame_to_string(&bytes, false);
+        assert_eq!(plain.lines().count(), 8);
+        assert_eq!(plain.lines().next(), Some("#......."));
+        assert_eq!(plain.lines().last(), Some(".......#"));
+        let ansi = frame_to_string(&bytes, true);
+        assert!(ansi.starts_with("\x1b[38;2;248;0;0m\u{2588}\u{2588}\x1b[38;2;0;0;0m"));
+        assert_eq!(ansi.matches("\x1b[0m\n").count(), 8);
+    }
+
+    #[cfg(feature = "led-matrix")]
+    #[test]
+    fn check_colours_rgb() {
+        let colour: Colour = (0x12, 0x34, 0x56).into();
+        assert_eq!(colour.rgb(), (0x12, 0x34, 0x56));
+        let rgb: (u8, u8, u8) = Colour::MAGENTA.into();
+        assert_eq!(rgb, (0xff, 0x00, 0xff));
+        // Going through the LED matrix's RGB565 loses the low bits
+        let shown = Colour::from_rgb565(colour.to_rgb565());
+        assert_eq!(shown.rgb(), (0x10, 0x34, 0x50));
+    }
+
+    #[cfg(feature = "led-matrix")]
+    #[test]
+    fn check_colours_hsv() {
+        assert_eq!(Colour::from_hsv(0.0, 1.0, 1.0), Colour::RED);
+        assert_eq!(Colour::from_hsv(120.0, 1.0, 1.0), Colour::GREEN);
+        assert_eq!(Colour::from_hsv(240.0, 1.0, 1.0), Colour::BLUE);
+        assert_eq!(Colour::from_hsv(-300.0, 1.0, 1.0), Colour::YELLOW);
+        assert_eq!(Colour::from_hsv(180.0, 0.0, 1.0), Colour::WHITE);
+        assert_eq!(Colour::from_hsv(180.0, 1.0, 0.0), Colour::BLACK);
+        assert_eq!(Colour::from_hsv(f32::NAN, 2.0, 1.0), Colour::RED);
+    }
+
+    #[cfg(feature = "led-matrix")]
+    #[test]
+    fn check_resize_rgb() {
+        // A 16x16 image, white on the left half and black on the right,
+        // except for one red pixel in the top-right 2x2 block
+        let mut data = vec![0u8; 16 * 16 * 3];
+        for y in 0..16 {
+            for x in 0..8 {
+                let offset = (y * 16 + x) * 3;
+                data[offset..offset + 3].copy_from_slice(&[0xff, 0xff, 0xff]);
+            }
+        }
+        data[15 * 3] = 0xff;
+        let bytes = resize_rgb(16, 16, &data).unwrap();
+        let plain = frame_to_string(&bytes, false);
+        assert_eq!(plain.lines().nth(1), Some("####...."));
+        // The red pixel is a quarter of its block
+        let quarter_red: Colour = (0x40, 0, 0).into();
+        assert_eq!(
+            u16::from(bytes[14]) | u16::from(bytes[15]) << 8,
+            quarter_red.to_rgb565()
+        );
+        // A 2x1 image is stretched with the nearest pixel
+        let bytes = resize_rgb(2, 1, &[0xff, 0, 0, 0, 0, 0xff]).unwrap();
+        assert_eq!(&bytes[0..2], &Colour::RED.0.rgb565());
+        assert_eq!(&bytes[126..128], &Colour::BLUE.0.rgb565());
+        assert!(resize_rgb(8, 8, &[0; 10]).is_err());
+        assert!(resize_rgb(0, 8, &[]).is_err());
+    }
+
+    #[cfg(feature = "led-matrix")]
+    #[test]
+    fn check_threshold_colour() {
+        assert_eq!(threshold_colour(4.0, 2.0, 8.0), Colour::GREEN);
+        assert_eq!(threshold_colour(2.0, 2.0, 8.0), Colour::AMBER);
+        assert_eq!(threshold_colour(7.7, 2.0, 8.0), Colour::AMBER);
+        assert_eq!(threshold_colour(8.5, 2.0, 8.0), Colour::AMBER);
+        assert_eq!(threshold_colour(9.0, 2.0, 8.0), Colour::RED);
+        assert_eq!(threshold_colour(-1.0, 2.0, 8.0), Colour::RED);
+        assert_eq!(threshold_colour(f64::NAN, 2.0, 8.0), Colour::RED);
+    }
+
+    #[cfg(feature = "led-matrix")]
+    #[test]
+    fn check_bar_rows() {
+        assert_eq!(bar_rows(0.0), 0);
+        assert_eq!(bar_rows(0.5), 4);
+        assert_eq!(bar_rows(0.3), 2);
+        assert_eq!(bar_rows(1.5), 8);
+        assert_eq!(bar_rows(-1.0), 0);
+        assert_eq!(bar_rows(f32::NAN), 0);
+    }
+
+    #[cfg(feature = "led-matrix")]
+    #[test]
+    fn check_detect_framebuffer() {
+        let sysfs = ::std::env::temp_dir().join(format!("sensehat-fb-{}", ::std::process::id()));
+        for &(fb, name) in &[("fb0", "BCM2708 FB"), ("fb2", "RPi-Sense FB")] {
+            ::std::fs::create_dir_all(sysfs.join(fb)).unwrap();
+            ::std::fs::write(sysfs.join(fb).join("name"), format!("{}\n", name)).unwrap();
+        }
+        let found = detect_framebuffer(&sysfs);
+        ::std::fs::remove_dir_all(&sysfs).unwrap();
+        assert_eq!(found, Some(String::from("/dev/fb2")));
+    }
+
+    #[test]
+    fn check_orientation_display() {
+        let orientation = Orientation {
+            roll: Angle::from_degrees(10.0),
+            pitch: Angle::from_degrees(-5.5),
+            yaw: Angle::from_radians(::std::f64::consts::PI),
+        };
+        assert_eq!(
+            format!("{}", orientation),
+            "roll=10.0°, pitch=-5.5°, yaw=180.0°"
+        );
+    }
+
+    #[test]
+    fn check_orientation_difference() {
+        let orientation = |degrees: f64| Orientation {
+            roll: Angle::from_degrees(degrees),
+            pitch: Angle::from_degrees(-degrees),
+            yaw: Angle::from_degrees(degrees / 2.0),
+        };
+        let (roll, pitch, yaw) = orientation(-179.0)
+            .difference(&orientation(179.0))
+            .as_degrees();
+        assert!((roll - 2.0).abs() < 1e-9);
+        assert!((pitch + 2.0).abs() < 1e-9);
+        assert!((yaw + 179.0).abs() < 1e-9);
+        let (roll, _, _) = orientation(10.0)
+            .difference(&orientation(30.0))
+            .as_degrees();
+        assert!((roll + 20.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn check_shaking() {
+        let still = Vector3D {
+            x: 0.0,
+            y: 0.1,
+            z: 1.0,
+        };
+        assert!(!is_shaking(still, 0.5));
+        assert!(is_shaking(still * 2.0, 0.5));
+        // Free fall counts too
+        assert!(is_shaking(still * 0.1, 0.5));
+    }
+
+    #[test]
+    fn check_quaternion() {
+        let quaternion = |roll: f64, pitch: f64, yaw: f64| {
+            Orientation {
+                roll: Angle::from_degrees(roll),
+                pitch: Angle::from_degrees(pitch),
+                yaw: Angle::from_degrees(yaw),
+            }
+            .to_quaternion()
+        };
+        let close = |a: [f64; 4], b: [f64; 4]| a.iter().zip(&b).all(|(a, b)| (a - b).abs() < 1e-9);
+        let half = ::std::f64::consts::FRAC_1_SQRT_2;
+        assert!(close(quaternion(0.0, 0.0, 0.0), [1.0, 0.0, 0.0, 0.0]));
+        assert!(close(quaternion(90.0, 0.0, 0.0), [half, half, 0.0, 0.0]));
+        assert!(close(quaternion(0.0, 90.0, 0.0), [half, 0.0, half, 0.0]));
+        assert!(close(quaternion(0.0, 0.0, 90.0), [half, 0.0, 0.0, half]));
+        // Yaw then pitch, each by 90 degrees
+        assert!(close(quaternion(0.0, 90.0, 90.0), [0.5, -0.5, 0.5, 0.5]));
+        let q = quaternion(12.0, -34.0, 156.0);
+        let norm: f64 = q.iter().map(|c| c * c).sum();
+        assert!((norm - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn check_is_level() {
+        let accel = |x: f64, y: f64, z: f64| Vector3D { x, y, z };
+        let tolerance = Angle::from_degrees(5.0);
+        assert!(is_level(accel(0.0, 0.0, 1.0), tolerance));
+        // About 2.9 degrees of roll
+        assert!(is_level(accel(0.0, 0.05, 1.0), tolerance));
+        // About 11 degrees of pitch
+        assert!(!is_level(accel(0.2, 0.0, 1.0), tolerance));
+        // Upside down
+        assert!(!is_level(accel(0.0, 0.0, -1.0), tolerance));
+    }
+
+    #[test]
+    fn check_true_heading() {
+        let heading = |magnetic: f64, declination: f64| {
+            heading_degrees(true_heading(Angle::from_degrees(magnetic), declination))
+        };
+        assert!((heading(10.0, 2.5) - 12.5).abs() < 1e-9);
+        assert!((heading(179.0, 3.0) - 182.0).abs() < 1e-9);
+        assert!((heading(1.0, -3.0) - 358.0).abs() < 1e-9);
+        let wrapped = true_heading(Angle::from_degrees(179.0), 3.0).as_degrees();
+        assert!((wrapped + 178.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn check_heading_degrees() {
+        assert_eq!(heading_degrees(Angle::from_degrees(-90.0)), 270.0);
+        assert_eq!(heading_degrees(Angle::from_degrees(180.0)), 180.0);
+        assert_eq!(heading_degrees(Angle::from_degrees(360.0)), 0.0);
+        assert_eq!(heading_degrees(Angle::from_degrees(-1e-20)), 0.0);
+    }
+
+    #[test]
+    fn check_tilt_compensation() {
+        // Facing east, rolled by 30 degrees
+        let (sin, cos) = (30.0f64).to_radians().sin_cos();
+        let accel = Vector3D {
+            x: 0.0,
+            y: sin,
+            z: cos,
+        };
+        let compass = Vector3D {
+            x: 0.0,
+            y: -cos + 0.5 * sin,
+            z: sin + 0.5 * cos,
+        };
+        let (roll, pitch, yaw) = absolute_pose(accel, compass);
+        assert!((roll.to_degrees() - 30.0).abs() < 1e-9);
+        assert!(pitch.abs() < 1e-9);
+        assert!((yaw.to_degrees() - 90.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn check_tilt_compensation_rolled_and_pitched() {
+        // The readings from a board at the given pose, in a field pointing
+        // north and, more steeply, down.
+        let readings = |roll: f64, pitch: f64, yaw: f64| {
+            let (sin_r, cos_r) = roll.to_radians().sin_cos();
+            let (sin_p, cos_p) = pitch.to_radians().sin_cos();
+            let (sin_y, cos_y) = yaw.to_radians().sin_cos();
+            let (x, y, z) = (cos_y, -sin_y, 2.0);
+            let accel = Vector3D {
+                x: -sin_p,
+                y: cos_p * sin_r,
+                z: cos_p * cos_r,
+            };
+            let compass = Vector3D {
+                x: x * cos_p - z * sin_p,
+                y: x * sin_p * sin_r + y * cos_r + z * cos_p * sin_r,
+                z: x * sin_p * cos_r - y * sin_r + z * cos_p * cos_r,
+            };
+            (accel, compass)
+        };
+        for &(roll, pitch, yaw) in &[
+            (30.0, 30.0, 60.0),
+            (40.0, -35.0, -160.0),
+            (-20.0, 50.0, 135.0),
+        ] {
+            let (accel, compass) = readings(roll, pitch, yaw);
+            let (r, p, y) = absolute_pose(accel, compass);
+            assert!((r.to_degrees() - roll).abs() < 1e-9);
+            assert!((p.to_degrees() - pitch).abs() < 1e-9);
+            let error = angle_difference(Angle::from_radians(y), Angle::from_degrees(yaw));
+            assert!(error.as_degrees().abs() < 1e-9, "{:?}", (roll, pitch, yaw));
+        }
+    }
+
+    #[test]
+    fn check_vector_magnitude() {
+        let v = Vector3D {
+            x: 2.0,
+            y: -3.0,
+            z: 6.0,
+        };
+        assert_eq!(v.magnitude(), 7.0);
+        assert!((v.normalized().magnitude() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn check_vector_cross() {
+        let x = Vector3D {
+            x: 1.0,
+            y: 0.0,
+            z: 0.0,
+        };
+        let y = Vector3D {
+            x: 0.0,
+            y: 1.0,
+            z: 0.0,
+        };
+        let z = x.cross(&y);
+        assert_eq!((z.x, z.y, z.z), (0.0, 0.0, 1.0));
+        let z = y.cross(&x);
+        assert_eq!((z.x, z.y, z.z), (0.0, 0.0, -1.0));
+        assert_eq!(x.dot(&y), 0.0);
+    }
+
+    #[test]
+    fn check_compass_calibration() {
+        let cal = CompassCalibration {
+            min: Vector3D {
+                x: -10.0,
+                y: 5.0,
+                z: 0.0,
+            },
+            max: Vector3D {
+                x: 30.0,
+                y: 5.0,
+                z: 0.0,
+            },
+        }
+        .include(Vector3D {
+            x: 0.0,
+            y: -15.0,
+            z: 40.0,
+        });
+        let offset = cal.offset();
+        assert_eq!(offset.x, 10.0);
+        assert_eq!(offset.y, -5.0);
+        assert_eq!(offset.z, 20.0);
+    }
+
+    #[cfg(feature = "led-matrix")]
+    #[test]
+    fn check_line_clipped() {
+        let mut bytes = [0u8; 128];
+        put_line(&mut bytes, -2, -2, 9, 9, Colour::WHITE);
+        for i in 0..64 {
+            let lit = bytes[i * 2] != 0;
+            assert_eq!(lit, i % 9 == 0, "pixel {}", i);
+        }
+    }
+
+    #[cfg(feature = "led-matrix")]
+    #[test]
+    fn check_line_extreme_coordinates() {
+        let lit = |bytes: &[u8; 128]| (0..64).filter(|i| bytes[i * 2] != 0).count();
+        let mut bytes = [0u8; 128];
+        put_line(
+            &mut bytes,
+            -2_000_000_000,
+            3,
+            2_000_000_000,
+            3,
+            Colour::WHITE,
+        );
+        assert_eq!(lit(&bytes), 8);
+        assert!((0..8).all(|x| bytes[(3 * 8 + x) * 2] != 0));
+        let mut bytes = [0u8; 128];
+        put_line(
+            &mut bytes,
+            i32::MIN,
+            i32::MIN,
+            i32::MAX,
+            i32::MAX,
+            Colour::WHITE,
+        );
+        assert_eq!(lit(&bytes), 8);
+        assert!((0..8).all(|i| bytes[i * 18] != 0));
+        let mut bytes = [0u8; 128];
+        put_line(&mut bytes, i32::MIN, 100, i32::MAX, 100, Colour::WHITE);
+        put_line(&mut bytes, 8, i32::MIN, 8, i32::MAX, Colour::WHITE);
+        assert_eq!(lit(&bytes), 0);
+    }
+
+    #[cfg(feature = "led-matrix")]
+    #[test]
+    fn check_rect_extreme_coordinates() {
+        let lit = |bytes: &[u8; 128]| (0..64).filter(|i| bytes[i * 2] != 0).count();
+        let mut bytes = [0u8; 128];
+        put_rect(&mut bytes, 0, 0, 8, 1_000_000_000, Colour::WHITE, true);
+        assert_eq!(lit(&bytes), 64);
+        let mut bytes = [0u8; 128];
+        put_rect(
+            &mut bytes,
+            i32::MIN,
+            i32::MIN,
+            i32::MAX,
+            i32::MAX,
+            Colour::WHITE,
+            true,
+        );
+        assert_eq!(lit(&bytes), 0);
+        // Only the left and top edges are on the matrix
+        let mut bytes = [0u8; 128];
+        put_rect(&mut bytes, 2, 2, i32::MAX, i32::MAX, Colour::WHITE, false);
+        assert_eq!(lit(&bytes), 11);
+        let mut bytes = [0u8; 128];
+        put_rect(&mut bytes, i32::MAX, 0, i32::MAX, 8, Colour::WHITE, false);
+        assert_eq!(lit(&bytes), 0);
+    }
 }
 
 // End of file
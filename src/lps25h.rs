@@ -4,19 +4,77 @@
 use byteorder::{ByteOrder, LittleEndian};
 use i2cdev::core::I2CDevice;
 
+/// The I2C address of the chip on the Sense HAT.
+pub const I2C_ADDRESS: u16 = 0x5c;
+/// The value of the WHO_AM_I register.
+pub const WHO_AM_I: u8 = 0xbd;
+/// The value of the WHO_AM_I register on the LPS22HB, which replaces the
+/// LPS25H on the Sense HAT v2.
+pub const WHO_AM_I_LPS22HB: u8 = 0xb1;
+
+pub const REG_REF_P_XL: u8 = 0x08;
+#[allow(dead_code)]
+pub const REG_REF_P_L: u8 = 0x09;
+#[allow(dead_code)]
+pub const REG_REF_P_H: u8 = 0x0a;
+pub const REG_WHO_AM_I: u8 = 0x0f;
 pub const REG_RES_CONF: u8 = 0x10;
 pub const REG_CTRL_REG_1: u8 = 0x20;
 pub const REG_CTRL_REG_2: u8 = 0x21;
 pub const REG_STATUS_REG: u8 = 0x27;
 pub const REG_PRESS_OUT_XL: u8 = 0x28;
+#[allow(dead_code)]
 pub const REG_PRESS_OUT_L: u8 = 0x29;
+#[allow(dead_code)]
 pub const REG_PRESS_OUT_H: u8 = 0x2a;
 pub const REG_TEMP_OUT_L: u8 = 0x2b;
+#[allow(dead_code)]
 pub const REG_TEMP_OUT_H: u8 = 0x2c;
 pub const REG_FIFO_CTRL: u8 = 0x2e;
 
+pub const CTRL_REG_1_PD: u8 = 0x80;
+pub const CTRL_REG_1_BDU: u8 = 0x04;
+pub const CTRL_REG_1_RESET_AZ: u8 = 0x02;
+pub const CTRL_REG_2_ONE_SHOT: u8 = 0x01;
+pub const CTRL_REG_2_BOOT: u8 = 0x80;
+pub const CTRL_REG_2_AUTO_ZERO: u8 = 0x02;
+
+/// Set in the sub-address to read multiple registers in one transaction.
+pub const AUTO_INCREMENT: u8 = 0x80;
+
+/// F_MODE = 110 in FIFO_CTRL selects FIFO mean mode.
+pub const FIFO_CTRL_MEAN_MODE: u8 = 0xc0;
+
+/// The number of samples in the moving average taken by the FIFO in mean
+/// mode (the WTM_POINT field of FIFO_CTRL). At the default data rate of
+/// 25 Hz, each reading is the mean of the last 80 ms (`Samples2`), 160 ms
+/// (`Samples4`), 320 ms (`Samples8`), 640 ms (`Samples16`) or 1.28 s
+/// (`Samples32`), and lags behind a change in pressure by about half that.
+/// Slower data rates lengthen these in proportion.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PressureAveraging {
+    Samples2 = 0x01,
+    Samples4 = 0x03,
+    Samples8 = 0x07,
+    Samples16 = 0x0f,
+    Samples32 = 0x1f,
+}
+
+/// How often the pressure sensor takes a reading (the ODR field of
+/// CTRL_REG1). Slower rates use less power.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PressureDataRate {
+    OneHz = 1,
+    SevenHz = 2,
+    TwelveHalfHz = 3,
+    TwentyFiveHz = 4,
+}
+
 pub(crate) struct Lps25h<T: I2CDevice + Sized> {
     i2cdev: T,
+    powered: bool,
+    fifo_ctrl: u8,
+    rate: PressureDataRate,
 }
 
 impl<T> Lps25h<T>
@@ -25,13 +83,109 @@ where
 {
     /// Create a new pressure sensor handle for the given path/addr.
     /// Init sequence from https://github.com/RPi-Distro/RTIMULib
-    pub fn new(mut i2cdev: T) -> Result<Lps25h<T>, T::Error> {
-        i2cdev.smbus_write_byte_data(REG_CTRL_REG_1, 0xc4)?;
-        i2cdev.smbus_write_byte_data(REG_RES_CONF, 0x05)?;
-        i2cdev.smbus_write_byte_data(REG_FIFO_CTRL, 0xc0)?;
-        i2cdev.smbus_write_byte_data(REG_CTRL_REG_2, 0x40)?;
+    pub fn new(i2cdev: T) -> Result<Lps25h<T>, T::Error> {
+        let mut lps25h = Lps25h {
+            i2cdev,
+            powered: false,
+            fifo_ctrl: FIFO_CTRL_MEAN_MODE,
+            rate: PressureDataRate::TwentyFiveHz,
+        };
+        lps25h.init()?;
+        Ok(lps25h)
+    }
+
+    /// Reboot the chip, reloading its trimming memory, and then re-run the
+    /// init sequence.
+    pub fn reset(&mut self) -> Result<(), T::Error> {
+        self.i2cdev
+            .smbus_write_byte_data(REG_CTRL_REG_2, CTRL_REG_2_BOOT)?;
+        // The BOOT bit clears itself once the reboot has finished
+        for _ in 0..10 {
+            if (self.i2cdev.smbus_read_byte_data(REG_CTRL_REG_2)? & CTRL_REG_2_BOOT) == 0 {
+                break;
+            }
+            ::std::thread::sleep(::std::time::Duration::from_millis(1));
+        }
+        self.init()
+    }
+
+    fn init(&mut self) -> Result<(), T::Error> {
+        self.i2cdev
+            .smbus_write_byte_data(REG_CTRL_REG_1, self.ctrl_reg_1())?;
+        self.powered = true;
+        self.i2cdev.smbus_write_byte_data(REG_RES_CONF, 0x05)?;
+        self.i2cdev
+            .smbus_write_byte_data(REG_FIFO_CTRL, self.fifo_ctrl)?;
+        self.i2cdev.smbus_write_byte_data(REG_CTRL_REG_2, 0x40)
+    }
+
+    /// Set the depth of the FIFO's moving average. This is kept across a
+    /// `reset()`.
+    pub fn set_averaging(&mut self, averaging: PressureAveraging) -> Result<(), T::Error> {
+        self.fifo_ctrl = FIFO_CTRL_MEAN_MODE | averaging as u8;
+        self.i2cdev
+            .smbus_write_byte_data(REG_FIFO_CTRL, self.fifo_ctrl)
+    }
+
+    /// Set how often the chip takes a reading. This is kept across a
+    /// `reset()`, and also puts the chip back into continuous mode after
+    /// `trigger_one_shot()`. It doesn't power the chip up if it is powered
+    /// down.
+    pub fn set_data_rate(&mut self, rate: PressureDataRate) -> Result<(), T::Error> {
+        self.rate = rate;
+        let ctrl1 = self.i2cdev.smbus_read_byte_data(REG_CTRL_REG_1)?;
+        let ctrl1 = (ctrl1 & CTRL_REG_1_PD) | (self.ctrl_reg_1() & !CTRL_REG_1_PD);
+        self.i2cdev.smbus_write_byte_data(REG_CTRL_REG_1, ctrl1)
+    }
+
+    /// CTRL_REG1 for continuous conversion at the chosen data rate.
+    fn ctrl_reg_1(&self) -> u8 {
+        CTRL_REG_1_PD | ((self.rate as u8) << 4) | CTRL_REG_1_BDU
+    }
+
+    /// Stop continuous conversion and trigger a single pressure and
+    /// temperature measurement. Poll `status()` to find out when it has
+    /// completed. The chip stays idle between one-shot measurements.
+    pub fn trigger_one_shot(&mut self) -> Result<(), T::Error> {
+        // Power on, but with ODR = 000 (one-shot mode) and BDU set.
+        self.i2cdev.smbus_write_byte_data(REG_CTRL_REG_1, 0x84)?;
+        let ctrl2 = self.i2cdev.smbus_read_byte_data(REG_CTRL_REG_2)?;
+        self.i2cdev
+            .smbus_write_byte_data(REG_CTRL_REG_2, ctrl2 | CTRL_REG_2_ONE_SHOT)
+    }
+
+    /// Put the chip into power-down mode, by clearing the PD bit in
+    /// CTRL_REG1. Does nothing if the chip is already powered down.
+    pub fn power_down(&mut self) -> Result<(), T::Error> {
+        if self.powered {
+            let ctrl1 = self.i2cdev.smbus_read_byte_data(REG_CTRL_REG_1)?;
+            self.i2cdev
+                .smbus_write_byte_data(REG_CTRL_REG_1, ctrl1 & !CTRL_REG_1_PD)?;
+            self.powered = false;
+        }
+        Ok(())
+    }
+
+    /// Bring the chip out of power-down mode, by setting the PD bit in
+    /// CTRL_REG1. Does nothing if the chip is already powered up.
+    pub fn power_up(&mut self) -> Result<(), T::Error> {
+        if !self.powered {
+            let ctrl1 = self.i2cdev.smbus_read_byte_data(REG_CTRL_REG_1)?;
+            self.i2cdev
+                .smbus_write_byte_data(REG_CTRL_REG_1, ctrl1 | CTRL_REG_1_PD)?;
+            self.powered = true;
+        }
+        Ok(())
+    }
 
-        Ok(Lps25h { i2cdev })
+    /// Read the WHO_AM_I register, which identifies the chip.
+    pub fn who_am_i(&mut self) -> Result<u8, T::Error> {
+        self.i2cdev.smbus_read_byte_data(REG_WHO_AM_I)
+    }
+
+    /// Is the chip powered up?
+    pub fn is_powered(&self) -> bool {
+        self.powered
     }
 
     /// Obtain the status bitfield from the chip.
@@ -41,10 +195,14 @@ where
 
     /// Obtain the temperature reading from the chip.
     /// T(°C) = 42.5 + (TEMP_OUT / 480)
+    ///
+    /// Both bytes are read in a single transaction, so they always come from
+    /// the same sample.
     pub fn get_temp(&mut self) -> Result<i16, T::Error> {
-        let mut buf = [0u8; 2];
-        buf[0] = self.i2cdev.smbus_read_byte_data(REG_TEMP_OUT_L)?;
-        buf[1] = self.i2cdev.smbus_read_byte_data(REG_TEMP_OUT_H)?;
+        let buf = self
+            .i2cdev
+            .smbus_read_i2c_block_data(REG_TEMP_OUT_L | AUTO_INCREMENT, 2)?;
+        debug!("LPS25H TEMP_OUT (0x{:02x}) = {:02x?}", REG_TEMP_OUT_L, buf);
         Ok(LittleEndian::read_i16(&buf))
     }
 
@@ -56,16 +214,139 @@ where
 
     /// Obtain the pressure reading from the chip.
     /// Pout(hPa) = PRESS_OUT / 4096
-    pub fn get_pressure(&mut self) -> Result<u32, T::Error> {
-        let mut buf = [0u8; 4];
-        buf[0] = self.i2cdev.smbus_read_byte_data(REG_PRESS_OUT_XL)?;
-        buf[1] = self.i2cdev.smbus_read_byte_data(REG_PRESS_OUT_L)?;
-        buf[2] = self.i2cdev.smbus_read_byte_data(REG_PRESS_OUT_H)?;
-        Ok(LittleEndian::read_u32(&buf))
+    ///
+    /// All three bytes are read in a single transaction, so they always come
+    /// from the same sample. The value is a signed 24-bit number.
+    pub fn get_pressure(&mut self) -> Result<i32, T::Error> {
+        let buf = self
+            .i2cdev
+            .smbus_read_i2c_block_data(REG_PRESS_OUT_XL | AUTO_INCREMENT, 3)?;
+        debug!(
+            "LPS25H PRESS_OUT (0x{:02x}) = {:02x?}",
+            REG_PRESS_OUT_XL, buf
+        );
+        Ok(pressure_from_bytes(&buf))
     }
 
     /// Obtain the pressure reading from the chip in hPa.
     pub fn get_pressure_hpa(&mut self) -> Result<f64, T::Error> {
         self.get_pressure().and_then(|c| Ok(f64::from(c) / 4096.0))
     }
+
+    /// Read the reference pressure from REF_P_XL, REF_P_L and REF_P_H: a
+    /// signed 24-bit value, in the same units as PRESS_OUT.
+    pub fn get_reference_pressure(&mut self) -> Result<i32, T::Error> {
+        let buf = self
+            .i2cdev
+            .smbus_read_i2c_block_data(REG_REF_P_XL | AUTO_INCREMENT, 3)?;
+        Ok(pressure_from_bytes(&buf))
+    }
+
+    /// Write the reference pressure registers. Values outside the signed
+    /// 24-bit range are clamped.
+    pub fn set_reference_pressure(&mut self, reference: i32) -> Result<(), T::Error> {
+        let mut buf = [0u8; 3];
+        LittleEndian::write_i24(&mut buf, reference.clamp(-0x80_0000, 0x7f_ffff));
+        self.i2cdev
+            .smbus_write_i2c_block_data(REG_REF_P_XL | AUTO_INCREMENT, &buf)
+    }
+
+    /// Turn auto-zero on, by setting AUTO_ZERO in CTRL_REG2: the chip copies
+    /// the current pressure into REF_P, and from then on PRESS_OUT is the
+    /// difference from it. Turning it off pulses RESET_AZ in CTRL_REG1 as
+    /// well, which sets REF_P back to zero, so PRESS_OUT is absolute again.
+    pub fn set_auto_zero(&mut self, enable: bool) -> Result<(), T::Error> {
+        let ctrl2 = self.i2cdev.smbus_read_byte_data(REG_CTRL_REG_2)?;
+        if enable {
+            return self
+                .i2cdev
+                .smbus_write_byte_data(REG_CTRL_REG_2, ctrl2 | CTRL_REG_2_AUTO_ZERO);
+        }
+        self.i2cdev
+            .smbus_write_byte_data(REG_CTRL_REG_2, ctrl2 & !CTRL_REG_2_AUTO_ZERO)?;
+        let ctrl1 = self.i2cdev.smbus_read_byte_data(REG_CTRL_REG_1)?;
+        self.i2cdev
+            .smbus_write_byte_data(REG_CTRL_REG_1, ctrl1 | CTRL_REG_1_RESET_AZ)?;
+        self.i2cdev.smbus_write_byte_data(REG_CTRL_REG_1, ctrl1)
+    }
+}
+
+/// Convert the PRESS_OUT_XL, PRESS_OUT_L and PRESS_OUT_H registers into a
+/// sign-extended 24-bit value.
+fn pressure_from_bytes(buf: &[u8]) -> i32 {
+    LittleEndian::read_i24(buf)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mock::MockI2CDevice;
+
+    #[test]
+    fn check_averaging() {
+        let mut lps25h = Lps25h::new(MockI2CDevice::lps25h()).unwrap();
+        assert_eq!(lps25h.i2cdev.register(REG_FIFO_CTRL), 0xc0);
+        lps25h.set_averaging(PressureAveraging::Samples8).unwrap();
+        assert_eq!(lps25h.i2cdev.register(REG_FIFO_CTRL), 0xc7);
+        lps25h.reset().unwrap();
+        assert_eq!(lps25h.i2cdev.register(REG_FIFO_CTRL), 0xc7);
+    }
+
+    #[test]
+    fn check_data_rate() {
+        let mut lps25h = Lps25h::new(MockI2CDevice::lps25h()).unwrap();
+        assert_eq!(lps25h.i2cdev.register(REG_CTRL_REG_1), 0xc4);
+        lps25h.set_data_rate(PressureDataRate::OneHz).unwrap();
+        assert_eq!(lps25h.i2cdev.register(REG_CTRL_REG_1), 0x94);
+        lps25h.trigger_one_shot().unwrap();
+        lps25h.set_data_rate(PressureDataRate::SevenHz).unwrap();
+        assert_eq!(lps25h.i2cdev.register(REG_CTRL_REG_1), 0xa4);
+        lps25h.power_down().unwrap();
+        lps25h
+            .set_data_rate(PressureDataRate::TwelveHalfHz)
+            .unwrap();
+        assert_eq!(lps25h.i2cdev.register(REG_CTRL_REG_1), 0x34);
+    }
+
+    #[test]
+    fn check_temperature_negative() {
+        // TEMP_OUT is a signed 16-bit value, so anything below 42.5 degrees C
+        // is a negative reading.
+        let mut lps25h = Lps25h::new(MockI2CDevice::lps25h()).unwrap();
+        assert_eq!(lps25h.get_temp().unwrap(), -8400);
+        assert!((lps25h.get_temp_celcius().unwrap() - 25.0).abs() < 1e-9);
+        lps25h.i2cdev.set_registers(REG_TEMP_OUT_L, &[0x00, 0x80]);
+        assert_eq!(lps25h.get_temp().unwrap(), -32768);
+        assert!(lps25h.get_temp_celcius().unwrap() < 42.5);
+        lps25h.i2cdev.set_registers(REG_TEMP_OUT_L, &[0xe0, 0x01]);
+        assert!((lps25h.get_temp_celcius().unwrap() - 43.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn check_reference_pressure() {
+        let mut lps25h = Lps25h::new(MockI2CDevice::lps25h()).unwrap();
+        assert_eq!(lps25h.get_reference_pressure().unwrap(), 0);
+        lps25h.set_reference_pressure(-2 * 4096).unwrap();
+        assert_eq!(lps25h.i2cdev.register(REG_REF_P_H), 0xff);
+        assert_eq!(lps25h.get_reference_pressure().unwrap(), -2 * 4096);
+        lps25h.set_reference_pressure(i32::MAX).unwrap();
+        assert_eq!(lps25h.get_reference_pressure().unwrap(), 0x7f_ffff);
+        lps25h.set_auto_zero(true).unwrap();
+        assert_eq!(lps25h.i2cdev.register(REG_CTRL_REG_2), 0x42);
+        lps25h.set_auto_zero(false).unwrap();
+        assert_eq!(lps25h.i2cdev.register(REG_CTRL_REG_2), 0x40);
+        assert_eq!(lps25h.i2cdev.register(REG_CTRL_REG_1), 0xc4);
+    }
+
+    #[test]
+    fn check_pressure_positive() {
+        // 1000 hPa
+        assert_eq!(pressure_from_bytes(&[0x00, 0x80, 0x3e]), 1000 * 4096);
+    }
+
+    #[test]
+    fn check_pressure_negative() {
+        assert_eq!(pressure_from_bytes(&[0xff, 0xff, 0xff]), -1);
+        assert_eq!(pressure_from_bytes(&[0x00, 0x00, 0x80]), -0x80_0000);
+    }
 }
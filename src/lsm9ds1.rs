@@ -9,15 +9,24 @@
 //! a C wrapper of the `RTIMULib` C++ API. We then call that unsafe C wrapper
 //! here, ensuring that any memory allocations were undone on drop.
 
+use std::ffi::CString;
 use std::fmt::Display;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::time::Duration;
 
-use super::{Angle, ImuData, Orientation, Vector3D};
+use super::imu::ImuBackend;
+use super::{Angle, ImuData, Orientation, SenseHatResult, Vector3D};
 use libc;
 
 enum RTIMULibContext {}
 
 extern "C" {
     fn rtimulib_wrapper_create() -> *mut RTIMULibContext;
+    fn rtimulib_wrapper_create_with_settings(
+        settings_directory: *const libc::c_char,
+        product_type: *const libc::c_char,
+    ) -> *mut RTIMULibContext;
     fn rtimulib_wrapper_destroy(p_context: *mut RTIMULibContext);
     fn rtimulib_set_sensors(
         p_context: *mut RTIMULibContext,
@@ -26,6 +35,7 @@ extern "C" {
         compass: libc::c_int,
     );
     fn rtimulib_wrapper_imu_read(p_context: *mut RTIMULibContext) -> libc::c_int;
+    fn rtimulib_wrapper_get_poll_interval(p_context: *const RTIMULibContext) -> libc::c_int;
     fn rtimulib_wrapper_get_imu_data(
         p_context: *mut RTIMULibContext,
         orientation: *mut CAllData,
@@ -63,12 +73,14 @@ struct CVector3D {
 #[derive(Debug)]
 pub enum Error {
     RTIMULibError,
+    InvalidSettingsPath,
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Error::RTIMULibError => write!(f, "RTIMULib error"),
+            Error::InvalidSettingsPath => write!(f, "Invalid RTIMULib settings file path"),
         }
     }
 }
@@ -81,9 +93,22 @@ pub(crate) struct Lsm9ds1<'a> {
 
 impl<'a> Lsm9ds1<'a> {
     /// Uses the `RTIMULib` library.
-    pub(crate) fn new() -> Result<Lsm9ds1<'a>, Error> {
+    ///
+    /// If `settings` is given, it is the path to an `RTIMULib` settings
+    /// file (e.g. `/home/pi/.config/sense_hat/RTIMULib.ini`). Otherwise
+    /// `RTIMULib.ini` is used from the usual places.
+    pub(crate) fn new(settings: Option<&Path>) -> Result<Lsm9ds1<'a>, Error> {
+        let settings = match settings {
+            Some(path) => Some(settings_path_parts(path)?),
+            None => None,
+        };
         let ctx_ref = unsafe {
-            let ctx_p = rtimulib_wrapper_create();
+            let ctx_p = match settings {
+                Some((ref directory, ref product_type)) => {
+                    rtimulib_wrapper_create_with_settings(directory.as_ptr(), product_type.as_ptr())
+                }
+                None => rtimulib_wrapper_create(),
+            };
             if ctx_p.is_null() {
                 return Err(Error::RTIMULibError);
             }
@@ -94,39 +119,44 @@ impl<'a> Lsm9ds1<'a> {
             rtimulib_ref: ctx_ref,
         })
     }
+}
 
-    /// Make the IMU do some work. When this function returns true, the IMU
-    /// has data we can fetch with `get_imu_data()`.
-    pub(crate) fn imu_read(&mut self) -> bool {
+impl<'a> ImuBackend for Lsm9ds1<'a> {
+    fn imu_read(&mut self) -> bool {
         let result = unsafe { rtimulib_wrapper_imu_read(self.rtimulib_ref) };
         result != 0
     }
 
-    pub(crate) fn set_fusion(&mut self) {
+    fn poll_interval(&self) -> Duration {
+        let ms = unsafe { rtimulib_wrapper_get_poll_interval(&*self.rtimulib_ref) };
+        Duration::from_millis(ms.max(1) as u64)
+    }
+
+    fn set_fusion(&mut self) {
         unsafe {
             rtimulib_set_sensors(self.rtimulib_ref, 1, 1, 1);
         }
     }
 
-    pub(crate) fn set_compass_only(&mut self) {
+    fn set_compass_only(&mut self) {
         unsafe {
             rtimulib_set_sensors(self.rtimulib_ref, 0, 0, 1);
         }
     }
 
-    pub(crate) fn set_gyro_only(&mut self) {
+    fn set_gyro_only(&mut self) {
         unsafe {
             rtimulib_set_sensors(self.rtimulib_ref, 1, 0, 0);
         }
     }
 
-    pub(crate) fn set_accel_only(&mut self) {
+    fn set_accel_only(&mut self) {
         unsafe {
             rtimulib_set_sensors(self.rtimulib_ref, 0, 1, 0);
         }
     }
 
-    pub(crate) fn get_imu_data(&mut self) -> Result<ImuData, Error> {
+    fn get_imu_data(&mut self) -> SenseHatResult<ImuData> {
         let mut temp = CAllData::default();
         let result = unsafe { rtimulib_wrapper_get_imu_data(self.rtimulib_ref, &mut temp) };
         if result != 0 {
@@ -185,11 +215,26 @@ impl<'a> Lsm9ds1<'a> {
                 },
             })
         } else {
-            Err(Error::RTIMULibError)
+            Err(Error::RTIMULibError.into())
         }
     }
 }
 
+/// `RTIMULib` wants a settings file as a directory and a product type, from
+/// which it builds `<directory>/<product type>.ini`.
+fn settings_path_parts(path: &Path) -> Result<(CString, CString), Error> {
+    let directory = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    let product_type = path.file_stem().ok_or(Error::InvalidSettingsPath)?;
+    let directory =
+        CString::new(directory.as_os_str().as_bytes()).map_err(|_| Error::InvalidSettingsPath)?;
+    let product_type =
+        CString::new(product_type.as_bytes()).map_err(|_| Error::InvalidSettingsPath)?;
+    Ok((directory, product_type))
+}
+
 impl<'a> Drop for Lsm9ds1<'a> {
     fn drop(&mut self) {
         unsafe { rtimulib_wrapper_destroy(self.rtimulib_ref) }
@@ -2,49 +2,59 @@
 //!
 //! This is just a placeholder so the the docs build without RTIMULib.
 
-use super::ImuData;
-use std::{fmt::Display, marker::PhantomData};
+use super::imu::ImuBackend;
+use super::{ImuData, SenseHatResult};
+use std::{fmt::Display, marker::PhantomData, path::Path, time::Duration};
 
 #[derive(Debug)]
 pub enum Error {
     RTIMULibError,
+    InvalidSettingsPath,
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Error::RTIMULibError => write!(f, "RTIMULib Error"),
+            Error::InvalidSettingsPath => write!(f, "Invalid RTIMULib settings file path"),
         }
     }
 }
 
+impl std::error::Error for Error {}
+
 pub(crate) struct Lsm9ds1<'a> {
     phantom: PhantomData<&'a u32>,
 }
 
 impl<'a> Lsm9ds1<'a> {
     /// Uses the `RTIMULib` library.
-    pub(crate) fn new() -> Result<Lsm9ds1<'a>, Error> {
+    pub(crate) fn new(_settings: Option<&Path>) -> Result<Lsm9ds1<'a>, Error> {
         Ok(Lsm9ds1 {
             phantom: PhantomData,
         })
     }
+}
 
-    /// Make the IMU do some work. When this function returns true, the IMU
-    /// has data we can fetch with `get_imu_data()`.
-    pub(crate) fn imu_read(&mut self) -> bool {
+impl<'a> ImuBackend for Lsm9ds1<'a> {
+    fn imu_read(&mut self) -> bool {
         false
     }
 
-    pub(crate) fn set_fusion(&mut self) {}
+    fn poll_interval(&self) -> Duration {
+        // What RTIMULib asks for at the LSM9DS1's default rate
+        Duration::from_millis(4)
+    }
+
+    fn set_fusion(&mut self) {}
 
-    pub(crate) fn set_compass_only(&mut self) {}
+    fn set_compass_only(&mut self) {}
 
-    pub(crate) fn set_gyro_only(&mut self) {}
+    fn set_gyro_only(&mut self) {}
 
-    pub(crate) fn set_accel_only(&mut self) {}
+    fn set_accel_only(&mut self) {}
 
-    pub(crate) fn get_imu_data(&mut self) -> Result<ImuData, Error> {
-        Err(Error::RTIMULibError)
+    fn get_imu_data(&mut self) -> SenseHatResult<ImuData> {
+        Err(Error::RTIMULibError.into())
     }
 }
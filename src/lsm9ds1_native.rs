@@ -0,0 +1,387 @@
+//! * Pure-Rust driver for the LSM9DS1 accelerometer
+//!
+//! See `http://www.st.com/en/mems-and-sensors/lsm9ds1.html`
+//!
+//! This talks to the chip directly over I2C, so it doesn't need `RTIMULib`.
+//! The orientation comes from a simple complementary filter: the gyroscope
+//! is integrated for short-term changes, and pulled towards the tilt from
+//! the accelerometer and the heading from the magnetometer to stop it
+//! drifting. It is noisier than `RTIMULib`'s fusion, and readings are in the
+//! chip's own axes, which may not match `RTIMULib`'s. It tracks Euler
+//! angles, so within a few degrees of straight up or down in pitch, where
+//! roll and yaw can't be told apart, it just follows the accelerometer and
+//! magnetometer.
+
+use super::imu::ImuBackend;
+use super::{
+    absolute_pose, check_who_am_i, Angle, ImuData, Orientation, SenseHatError, SenseHatResult,
+    Vector3D,
+};
+use byteorder::{ByteOrder, LittleEndian};
+use i2cdev::core::I2CDevice;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// The I2C address of the accelerometer and gyroscope on the Sense HAT.
+pub const AG_I2C_ADDRESS: u16 = 0x6a;
+/// The I2C address of the magnetometer on the Sense HAT.
+pub const MAG_I2C_ADDRESS: u16 = 0x1c;
+/// The value of the accelerometer and gyroscope's WHO_AM_I register.
+pub const AG_WHO_AM_I: u8 = 0x68;
+/// The value of the magnetometer's WHO_AM_I register.
+pub const MAG_WHO_AM_I: u8 = 0x3d;
+
+pub const REG_WHO_AM_I: u8 = 0x0f;
+pub const REG_CTRL_REG1_G: u8 = 0x10;
+pub const REG_STATUS_REG: u8 = 0x17;
+pub const REG_OUT_X_L_G: u8 = 0x18;
+pub const REG_CTRL_REG6_XL: u8 = 0x20;
+pub const REG_CTRL_REG8: u8 = 0x22;
+pub const REG_OUT_X_L_XL: u8 = 0x28;
+
+pub const REG_CTRL_REG1_M: u8 = 0x20;
+pub const REG_CTRL_REG2_M: u8 = 0x21;
+pub const REG_CTRL_REG3_M: u8 = 0x22;
+pub const REG_CTRL_REG4_M: u8 = 0x23;
+pub const REG_STATUS_REG_M: u8 = 0x27;
+pub const REG_OUT_X_L_M: u8 = 0x28;
+
+pub const STATUS_XLDA: u8 = 0x01;
+pub const STATUS_GDA: u8 = 0x02;
+pub const STATUS_M_ZYXDA: u8 = 0x08;
+
+/// Set on a magnetometer register address to read several registers in one
+/// go. The accelerometer and gyroscope use IF_ADD_INC in CTRL_REG8 instead.
+pub const MAG_AUTO_INCREMENT: u8 = 0x80;
+
+/// Gyroscope sensitivity at ±500 dps, in degrees per second per LSB.
+const GYRO_SCALE: f64 = 0.0175;
+/// Accelerometer sensitivity at ±8 g, in g per LSB.
+const ACCEL_SCALE: f64 = 0.000_244;
+/// Magnetometer sensitivity at ±4 gauss, in microteslas per LSB.
+const MAG_SCALE: f64 = 0.014;
+
+/// How much the filter trusts the integrated gyroscope over the
+/// accelerometer and magnetometer on each sample.
+const FILTER_GAIN: f64 = 0.98;
+
+pub(crate) struct Lsm9ds1<D: I2CDevice + Sized> {
+    ag_dev: D,
+    mag_dev: D,
+    gyro: bool,
+    accel: bool,
+    compass: bool,
+    /// The filtered (roll, pitch, yaw), in radians.
+    pose: Option<(f64, f64, f64)>,
+    last_sample: Option<Instant>,
+    data: ImuData,
+    error: Option<SenseHatError>,
+}
+
+impl<D> Lsm9ds1<D>
+where
+    D: I2CDevice + Sized,
+    SenseHatError: From<D::Error>,
+{
+    /// Checks the chips are there and starts them sampling at 119 Hz (80 Hz
+    /// for the magnetometer).
+    pub(crate) fn new(mut ag_dev: D, mut mag_dev: D) -> SenseHatResult<Lsm9ds1<D>> {
+        check_who_am_i(&mut ag_dev, "LSM9DS1", REG_WHO_AM_I, AG_WHO_AM_I)?;
+        check_who_am_i(
+            &mut mag_dev,
+            "LSM9DS1 magnetometer",
+            REG_WHO_AM_I,
+            MAG_WHO_AM_I,
+        )?;
+        // Gyro at 119 Hz, ±500 dps
+        ag_dev.smbus_write_byte_data(REG_CTRL_REG1_G, 0x68)?;
+        // Accel at 119 Hz, ±8 g
+        ag_dev.smbus_write_byte_data(REG_CTRL_REG6_XL, 0x78)?;
+        // Block data update, register address auto-increment
+        ag_dev.smbus_write_byte_data(REG_CTRL_REG8, 0x44)?;
+        // Magnetometer X/Y high-performance at 80 Hz, ±4 gauss, continuous,
+        // Z high-performance
+        mag_dev.smbus_write_byte_data(REG_CTRL_REG1_M, 0x7c)?;
+        mag_dev.smbus_write_byte_data(REG_CTRL_REG2_M, 0x00)?;
+        mag_dev.smbus_write_byte_data(REG_CTRL_REG3_M, 0x00)?;
+        mag_dev.smbus_write_byte_data(REG_CTRL_REG4_M, 0x0c)?;
+        Ok(Lsm9ds1 {
+            ag_dev,
+            mag_dev,
+            gyro: true,
+            accel: true,
+            compass: true,
+            pose: None,
+            last_sample: None,
+            data: ImuData::default(),
+            error: None,
+        })
+    }
+
+    fn set_sensors(&mut self, gyro: bool, accel: bool, compass: bool) {
+        self.gyro = gyro;
+        self.accel = accel;
+        self.compass = compass;
+    }
+
+    /// Reads whichever enabled sensors have new data. Returns false if none
+    /// of them did.
+    fn read(&mut self) -> SenseHatResult<bool> {
+        let status = self.ag_dev.smbus_read_byte_data(REG_STATUS_REG)?;
+        let gyro = if self.gyro && (status & STATUS_GDA) != 0 {
+            Some(read_vector(&mut self.ag_dev, REG_OUT_X_L_G)? * GYRO_SCALE.to_radians())
+        } else {
+            None
+        };
+        let accel = if self.accel && (status & STATUS_XLDA) != 0 {
+            Some(read_vector(&mut self.ag_dev, REG_OUT_X_L_XL)? * ACCEL_SCALE)
+        } else {
+            None
+        };
+        let compass = if self.compass
+            && (self.mag_dev.smbus_read_byte_data(REG_STATUS_REG_M)? & STATUS_M_ZYXDA) != 0
+        {
+            Some(read_vector(&mut self.mag_dev, REG_OUT_X_L_M | MAG_AUTO_INCREMENT)? * MAG_SCALE)
+        } else {
+            None
+        };
+        if gyro.is_none() && accel.is_none() && compass.is_none() {
+            return Ok(false);
+        }
+
+        let now = Instant::now();
+        let fusion = self.gyro && self.accel && self.compass;
+        let fusion_pose = match (fusion, gyro, accel, compass) {
+            (true, Some(gyro), Some(accel), Some(compass)) => {
+                let dt = self.last_sample.map(|t| {
+                    let elapsed = now.duration_since(t);
+                    elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) * 1e-9
+                });
+                self.last_sample = Some(now);
+                let pose = filter(self.pose, dt, gyro, accel, compass);
+                self.pose = Some(pose);
+                Some(Orientation {
+                    roll: Angle::from_radians(pose.0),
+                    pitch: Angle::from_radians(pose.1),
+                    yaw: Angle::from_radians(pose.2),
+                })
+            }
+            _ => None,
+        };
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() * 1_000_000 + u64::from(d.subsec_micros()))
+            .unwrap_or(0);
+        self.data = ImuData {
+            timestamp,
+            fusion_pose,
+            gyro,
+            accel,
+            compass,
+            pressure: None,
+            temperature: None,
+            humidity: None,
+        };
+        Ok(true)
+    }
+}
+
+impl<D> ImuBackend for Lsm9ds1<D>
+where
+    D: I2CDevice + Sized,
+    SenseHatError: From<D::Error>,
+{
+    fn imu_read(&mut self) -> bool {
+        match self.read() {
+            Ok(ready) => ready,
+            Err(e) => {
+                // Report the error from get_imu_data
+                self.error = Some(e);
+                true
+            }
+        }
+    }
+
+    fn poll_interval(&self) -> Duration {
+        // The gyro and accelerometer sample at 119 Hz
+        Duration::from_secs(1) / 119
+    }
+
+    fn get_imu_data(&mut self) -> SenseHatResult<ImuData> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => Ok(self.data.clone()),
+        }
+    }
+
+    fn set_fusion(&mut self) {
+        self.set_sensors(true, true, true);
+    }
+
+    fn set_compass_only(&mut self) {
+        self.set_sensors(false, false, true);
+    }
+
+    fn set_gyro_only(&mut self) {
+        self.set_sensors(true, false, false);
+    }
+
+    fn set_accel_only(&mut self) {
+        self.set_sensors(false, true, false);
+    }
+}
+
+/// Reads three little-endian i16s, X then Y then Z.
+fn read_vector<D>(dev: &mut D, register: u8) -> SenseHatResult<Vector3D>
+where
+    D: I2CDevice,
+    SenseHatError: From<D::Error>,
+{
+    let buf = dev.smbus_read_i2c_block_data(register, 6)?;
+    if buf.len() < 6 {
+        return Err(SenseHatError::GenericError);
+    }
+    Ok(Vector3D {
+        x: f64::from(LittleEndian::read_i16(&buf[0..2])),
+        y: f64::from(LittleEndian::read_i16(&buf[2..4])),
+        z: f64::from(LittleEndian::read_i16(&buf[4..6])),
+    })
+}
+
+/// One step of the complementary filter. With no previous pose, or no time
+/// step, the filter starts again from the accelerometer and magnetometer.
+fn filter(
+    pose: Option<(f64, f64, f64)>,
+    dt: Option<f64>,
+    gyro: Vector3D,
+    accel: Vector3D,
+    compass: Vector3D,
+) -> (f64, f64, f64) {
+    let target = absolute_pose(accel, compass);
+    match (pose, dt) {
+        (Some(pose), Some(dt)) if dt < 1.0 => match euler_rates(pose, gyro) {
+            Some(rates) => (
+                blend(pose.0 + rates.0 * dt, target.0),
+                blend(pose.1 + rates.1 * dt, target.1),
+                blend(pose.2 + rates.2 * dt, target.2),
+            ),
+            None => target,
+        },
+        _ => target,
+    }
+}
+
+/// Converts the gyroscope's rates about the board's own axes into rates of
+/// change of (roll, pitch, yaw) at `pose`. There are none near ±90° of
+/// pitch, where the roll and yaw axes line up.
+fn euler_rates(pose: (f64, f64, f64), gyro: Vector3D) -> Option<(f64, f64, f64)> {
+    let (sin_r, cos_r) = pose.0.sin_cos();
+    let cos_p = pose.1.cos();
+    if cos_p.abs() < 0.05 {
+        return None;
+    }
+    let tan_p = pose.1.tan();
+    Some((
+        gyro.x + (gyro.y * sin_r + gyro.z * cos_r) * tan_p,
+        gyro.y * cos_r - gyro.z * sin_r,
+        (gyro.y * sin_r + gyro.z * cos_r) / cos_p,
+    ))
+}
+
+/// Moves `angle` a little towards `target`, the short way round the circle,
+/// and wraps the result into -π..π.
+fn blend(angle: f64, target: f64) -> f64 {
+    use std::f64::consts::PI;
+    let mut diff = target - angle;
+    while diff > PI {
+        diff -= 2.0 * PI;
+    }
+    while diff < -PI {
+        diff += 2.0 * PI;
+    }
+    let mut result = angle + (1.0 - FILTER_GAIN) * diff;
+    while result > PI {
+        result -= 2.0 * PI;
+    }
+    while result < -PI {
+        result += 2.0 * PI;
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mock::MockI2CDevice;
+    use std::f64::consts::FRAC_PI_2;
+
+    #[test]
+    fn check_mock_read() {
+        let mut ag_dev = MockI2CDevice::new();
+        ag_dev.set_register(REG_WHO_AM_I, AG_WHO_AM_I);
+        ag_dev.set_register(REG_STATUS_REG, STATUS_XLDA | STATUS_GDA);
+        // 4096 counts is very nearly 1 g, straight down the Z axis
+        ag_dev.set_registers(REG_OUT_X_L_XL, &[0, 0, 0, 0, 0x00, 0x10]);
+        let mut mag_dev = MockI2CDevice::new();
+        mag_dev.set_register(REG_WHO_AM_I, MAG_WHO_AM_I);
+        mag_dev.set_register(REG_STATUS_REG_M, STATUS_M_ZYXDA);
+        mag_dev.set_registers(REG_OUT_X_L_M, &[0x00, 0x10, 0, 0, 0, 0]);
+        let mut imu = Lsm9ds1::new(ag_dev, mag_dev).unwrap();
+        imu.set_fusion();
+        assert!(imu.imu_read());
+        let data = imu.get_imu_data().unwrap();
+        let accel = data.accel.unwrap();
+        assert!((accel.z - 0.999424).abs() < 1e-6);
+        let pose = data.fusion_pose.unwrap();
+        assert!(pose.roll.as_radians().abs() < 1e-9);
+        assert!(pose.pitch.as_radians().abs() < 1e-9);
+        assert!(pose.yaw.as_radians().abs() < 1e-9);
+    }
+
+    #[test]
+    fn check_level_pose() {
+        let accel = Vector3D {
+            x: 0.0,
+            y: 0.0,
+            z: 1.0,
+        };
+        let north = Vector3D {
+            x: 20.0,
+            y: 0.0,
+            z: -40.0,
+        };
+        let (roll, pitch, yaw) = absolute_pose(accel, north);
+        assert!(roll.abs() < 1e-9);
+        assert!(pitch.abs() < 1e-9);
+        assert!(yaw.abs() < 1e-9);
+    }
+
+    #[test]
+    fn check_euler_rates() {
+        let gyro = Vector3D {
+            x: 0.1,
+            y: 0.2,
+            z: 0.3,
+        };
+        // Level, the board's axes are the roll, pitch and yaw axes
+        let rates = euler_rates((0.0, 0.0, 1.0), gyro).unwrap();
+        assert!((rates.0 - 0.1).abs() < 1e-9);
+        assert!((rates.1 - 0.2).abs() < 1e-9);
+        assert!((rates.2 - 0.3).abs() < 1e-9);
+        // On its side, turning about the board's y axis changes the yaw
+        let rates = euler_rates((FRAC_PI_2, 0.0, 0.0), gyro).unwrap();
+        assert!((rates.0 - 0.1).abs() < 1e-9);
+        assert!((rates.1 + 0.3).abs() < 1e-9);
+        assert!((rates.2 - 0.2).abs() < 1e-9);
+        assert!(euler_rates((0.0, FRAC_PI_2, 0.0), gyro).is_none());
+    }
+
+    #[test]
+    fn check_blend_wraps() {
+        use std::f64::consts::PI;
+        // Just either side of ±π, the short way round is across the wrap
+        let angle = blend(PI - 0.01, -PI + 0.01);
+        assert!(angle.abs() > PI - 0.01);
+    }
+}
+
+// End of file
@@ -0,0 +1,174 @@
+//! * A fake I2C device, for testing without a Sense HAT
+//!
+//! The device is just a bank of 128 registers. Writing a register address
+//! selects it, and each byte read or written moves on to the next register,
+//! as the HTS221 and LPS25H do when the top (auto-increment) bit of the
+//! address is set. Use `MockI2CDevice::hts221()` and
+//! `MockI2CDevice::lps25h()`, with `SenseHat::with_devices`, to get a
+//! `SenseHat` which returns fixed readings.
+
+use hts221;
+use i2cdev::core::I2CDevice;
+use i2cdev::linux::LinuxI2CError;
+use lps25h;
+use std::io;
+
+/// A fake I2C device, backed by an array of registers.
+pub struct MockI2CDevice {
+    registers: [u8; 128],
+    address: usize,
+}
+
+impl MockI2CDevice {
+    /// Creates a device with every register set to zero.
+    pub fn new() -> MockI2CDevice {
+        MockI2CDevice {
+            registers: [0; 128],
+            address: 0,
+        }
+    }
+
+    /// Creates a device which looks like an HTS221 humidity sensor, with
+    /// calibration data and a reading of 25.0 degrees C and 50% relative
+    /// humidity ready.
+    pub fn hts221() -> MockI2CDevice {
+        let mut dev = MockI2CDevice::new();
+        dev.set_register(hts221::REG_WHO_AM_I, hts221::WHO_AM_I);
+        // 20 degrees C at 0 counts, 40 degrees C at 2000 counts
+        dev.set_register(hts221::REG_T0_C_8, 160);
+        // T1 is 320, split across two registers
+        dev.set_register(hts221::REG_T1_C_8, 0x40);
+        dev.set_register(hts221::REG_T1_T0, 0x04);
+        dev.set_registers(hts221::REG_T0_OUT, &[0, 0]);
+        dev.set_registers(hts221::REG_T1_OUT, &[0xd0, 0x07]);
+        // 20% at 0 counts, 80% at 6000 counts
+        dev.set_register(hts221::REG_H0_H_2, 40);
+        dev.set_register(hts221::REG_H1_H_2, 160);
+        dev.set_registers(hts221::REG_H0_T0_OUT, &[0, 0]);
+        dev.set_registers(hts221::REG_H1_T0_OUT, &[0x70, 0x17]);
+        // 25 degrees C and 50%
+        dev.set_registers(hts221::REG_TEMP_OUT_L, &[0xf4, 0x01]);
+        dev.set_registers(hts221::REG_HUMIDITY_OUT_L, &[0xb8, 0x0b]);
+        dev.set_register(hts221::REG_STATUS, 0x03);
+        dev
+    }
+
+    /// Creates a device which looks like an LPS25H pressure sensor, with a
+    /// reading of 25.0 degrees C and 1013.25 hPa ready.
+    pub fn lps25h() -> MockI2CDevice {
+        let mut dev = MockI2CDevice::new();
+        dev.set_register(lps25h::REG_WHO_AM_I, lps25h::WHO_AM_I);
+        // (25 - 42.5) * 480 = -8400
+        dev.set_registers(lps25h::REG_TEMP_OUT_L, &[0x30, 0xdf]);
+        // 1013.25 * 4096 = 0x3f5400
+        dev.set_registers(lps25h::REG_PRESS_OUT_XL, &[0x00, 0x54, 0x3f]);
+        dev.set_register(lps25h::REG_STATUS_REG, 0x03);
+        dev
+    }
+
+    /// Returns the value of a register.
+    pub fn register(&self, register: u8) -> u8 {
+        self.registers[usize::from(register & 0x7f)]
+    }
+
+    /// Sets the value of a register.
+    pub fn set_register(&mut self, register: u8, value: u8) {
+        self.set_registers(register, &[value]);
+    }
+
+    /// Sets the values of consecutive registers, starting at `register`.
+    pub fn set_registers(&mut self, register: u8, values: &[u8]) {
+        let start = usize::from(register & 0x7f);
+        self.registers[start..start + values.len()].copy_from_slice(values);
+    }
+
+    fn next_register(&mut self) -> io::Result<&mut u8> {
+        let address = self.address;
+        self.address += 1;
+        self.registers
+            .get_mut(address)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "register out of range"))
+    }
+}
+
+impl Default for MockI2CDevice {
+    fn default() -> MockI2CDevice {
+        MockI2CDevice::new()
+    }
+}
+
+impl I2CDevice for MockI2CDevice {
+    type Error = LinuxI2CError;
+
+    fn read(&mut self, data: &mut [u8]) -> Result<(), LinuxI2CError> {
+        for byte in data.iter_mut() {
+            *byte = *self.next_register()?;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), LinuxI2CError> {
+        if let Some((&register, values)) = data.split_first() {
+            self.address = usize::from(register & 0x7f);
+            for &value in values {
+                *self.next_register()? = value;
+            }
+        }
+        Ok(())
+    }
+
+    fn smbus_write_quick(&mut self, _bit: bool) -> Result<(), LinuxI2CError> {
+        Ok(())
+    }
+
+    fn smbus_read_block_data(&mut self, _register: u8) -> Result<Vec<u8>, LinuxI2CError> {
+        Err(LinuxI2CError::Io(unsupported()))
+    }
+
+    fn smbus_read_i2c_block_data(
+        &mut self,
+        register: u8,
+        len: u8,
+    ) -> Result<Vec<u8>, LinuxI2CError> {
+        let mut data = vec![0; usize::from(len)];
+        self.write(&[register])?;
+        self.read(&mut data)?;
+        Ok(data)
+    }
+
+    fn smbus_write_block_data(
+        &mut self,
+        _register: u8,
+        _values: &[u8],
+    ) -> Result<(), LinuxI2CError> {
+        Err(LinuxI2CError::Io(unsupported()))
+    }
+
+    fn smbus_write_i2c_block_data(
+        &mut self,
+        register: u8,
+        values: &[u8],
+    ) -> Result<(), LinuxI2CError> {
+        let mut data = Vec::with_capacity(values.len() + 1);
+        data.push(register);
+        data.extend_from_slice(values);
+        self.write(&data)
+    }
+
+    fn smbus_process_block(
+        &mut self,
+        _register: u8,
+        _values: &[u8],
+    ) -> Result<Vec<u8>, LinuxI2CError> {
+        Err(LinuxI2CError::Io(unsupported()))
+    }
+}
+
+fn unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "SMBus block transfers are not supported",
+    )
+}
+
+// End of file
@@ -0,0 +1,151 @@
+//! * Tracking whether the air pressure is rising or falling
+//!
+//! The change in pressure over the last few hours says more about the
+//! coming weather than the pressure itself. `PressureTrend` keeps the recent
+//! readings and fits a straight line through them.
+
+use measurements::Pressure;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// At most this many samples are kept, however often they are added. The
+/// oldest are dropped first.
+const MAX_SAMPLES: usize = 1024;
+
+/// A change of less than this, in hPa per hour, counts as steady: 1 hPa in
+/// three hours.
+const STEADY_HPA_PER_HOUR: f64 = 1.0 / 3.0;
+
+/// Which way the pressure is heading.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Tendency {
+    Rising,
+    Steady,
+    Falling,
+}
+
+/// The pressure readings from the last `window`, for working out the trend.
+#[derive(Debug, Clone)]
+pub struct PressureTrend {
+    window: Duration,
+    samples: VecDeque<(Instant, f64)>,
+}
+
+impl PressureTrend {
+    /// Creates an empty tracker, which keeps samples for `window`.
+    pub fn new(window: Duration) -> PressureTrend {
+        PressureTrend {
+            window,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Changes how long samples are kept for.
+    pub fn set_window(&mut self, window: Duration) {
+        self.window = window;
+        if let Some(&(latest, _)) = self.samples.back() {
+            self.expire(latest);
+        }
+    }
+
+    /// Records a reading taken at `time`, and forgets any older than the
+    /// window.
+    pub fn add(&mut self, time: Instant, pressure: Pressure) {
+        self.samples.push_back((time, pressure.as_hectopascals()));
+        if self.samples.len() > MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.expire(time);
+    }
+
+    /// Returns how many samples are being kept.
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Returns true if there are no samples yet.
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Returns the rate of change, in hPa per hour, from a least-squares
+    /// straight line through the samples. Returns `None` until there are
+    /// at least two samples, taken at different times.
+    pub fn slope_hpa_per_hour(&self) -> Option<f64> {
+        let &(start, _) = self.samples.front()?;
+        let n = self.samples.len() as f64;
+        let hours = |t: Instant| t.duration_since(start).as_secs_f64() / 3600.0;
+        let mean_t = self.samples.iter().map(|&(t, _)| hours(t)).sum::<f64>() / n;
+        let mean_p = self.samples.iter().map(|&(_, p)| p).sum::<f64>() / n;
+        let mut covariance = 0.0;
+        let mut variance = 0.0;
+        for &(t, p) in &self.samples {
+            let dt = hours(t) - mean_t;
+            covariance += dt * (p - mean_p);
+            variance += dt * dt;
+        }
+        if variance > 0.0 {
+            Some(covariance / variance)
+        } else {
+            None
+        }
+    }
+
+    /// Classifies the slope from `slope_hpa_per_hour`: anything less than
+    /// 1 hPa in three hours either way is steady.
+    pub fn tendency(&self) -> Option<Tendency> {
+        self.slope_hpa_per_hour().map(|slope| {
+            if slope >= STEADY_HPA_PER_HOUR {
+                Tendency::Rising
+            } else if slope <= -STEADY_HPA_PER_HOUR {
+                Tendency::Falling
+            } else {
+                Tendency::Steady
+            }
+        })
+    }
+
+    fn expire(&mut self, now: Instant) {
+        while let Some(&(time, _)) = self.samples.front() {
+            if now.duration_since(time) > self.window {
+                self.samples.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn check_trend() {
+        let start = Instant::now();
+        let minutes = |m: u64| start + Duration::from_secs(m * 60);
+        let mut trend = PressureTrend::new(Duration::from_secs(3 * 3600));
+        assert_eq!(trend.tendency(), None);
+        trend.add(start, Pressure::from_hectopascals(1000.0));
+        assert_eq!(trend.slope_hpa_per_hour(), None);
+        // Falling 2 hPa an hour
+        for m in 1..=60 {
+            trend.add(
+                minutes(m),
+                Pressure::from_hectopascals(1000.0 - m as f64 / 30.0),
+            );
+        }
+        assert!((trend.slope_hpa_per_hour().unwrap() + 2.0).abs() < 1e-6);
+        assert_eq!(trend.tendency(), Some(Tendency::Falling));
+        // Three hours later the old samples have gone, and it's steady
+        for m in 240..=300 {
+            trend.add(minutes(m), Pressure::from_hectopascals(990.0));
+        }
+        assert_eq!(trend.len(), 61);
+        assert_eq!(trend.tendency(), Some(Tendency::Steady));
+        trend.set_window(Duration::from_secs(30 * 60));
+        assert_eq!(trend.len(), 31);
+    }
+}
+
+// End of file
@@ -0,0 +1,116 @@
+//! * Retrying sensor reads after transient I2C errors
+//!
+//! Electrical noise or a busy bus can make a single transfer fail with an
+//! error like `EREMOTEIO`, even though the next one will work. Those errors
+//! are retried; ones that mean the sensor has gone (like `ENODEV`) are not.
+
+use super::{SenseHatError, SenseHatResult};
+use i2cdev::linux::LinuxI2CError;
+use std::time::Duration;
+
+/// Linux errno values which are worth retrying.
+const EINTR: i32 = 4;
+const EIO: i32 = 5;
+const EAGAIN: i32 = 11;
+const EBUSY: i32 = 16;
+const ETIMEDOUT: i32 = 110;
+const EREMOTEIO: i32 = 121;
+
+/// How many times to try a sensor read which fails with a transient I2C
+/// error, and how long to wait before the first retry. The wait doubles
+/// after each failed attempt.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The total number of attempts, including the first. Zero is treated
+    /// as one.
+    pub attempts: u32,
+    /// How long to wait before the first retry.
+    pub delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Never retries, so every error is returned straight away.
+    pub fn none() -> RetryPolicy {
+        RetryPolicy {
+            attempts: 1,
+            delay: Duration::from_millis(0),
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    /// Three attempts, waiting 5 ms and then 10 ms between them.
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            attempts: 3,
+            delay: Duration::from_millis(5),
+        }
+    }
+}
+
+/// Calls `read` until it succeeds, fails with an error which isn't
+/// transient, or has been tried `policy.attempts` times.
+pub(crate) fn retry<T, F>(policy: RetryPolicy, mut read: F) -> SenseHatResult<T>
+where
+    F: FnMut() -> SenseHatResult<T>,
+{
+    let mut delay = policy.delay;
+    let mut attempt = 1;
+    loop {
+        let err = match read() {
+            Err(SenseHatError::I2CError(err)) => err,
+            result => return result,
+        };
+        // Turning the error into an io::Error keeps the errno, which a nix
+        // error doesn't otherwise let us see.
+        let err = ::std::io::Error::from(err);
+        if attempt >= policy.attempts || !is_transient(&err) {
+            return Err(SenseHatError::I2CError(LinuxI2CError::Io(err)));
+        }
+        debug!("Retrying after I2C error (attempt {}): {}", attempt, err);
+        ::std::thread::sleep(delay);
+        delay *= 2;
+        attempt += 1;
+    }
+}
+
+fn is_transient(err: &::std::io::Error) -> bool {
+    matches!(
+        err.raw_os_error(),
+        Some(EINTR | EIO | EAGAIN | EBUSY | ETIMEDOUT | EREMOTEIO)
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io;
+
+    fn failing(count: &mut u32, errno: i32) -> SenseHatResult<u32> {
+        *count += 1;
+        if *count < 3 {
+            Err(LinuxI2CError::Io(io::Error::from_raw_os_error(errno)).into())
+        } else {
+            Ok(*count)
+        }
+    }
+
+    #[test]
+    fn check_retry() {
+        let policy = RetryPolicy {
+            attempts: 3,
+            delay: Duration::from_millis(1),
+        };
+        let mut count = 0;
+        assert_eq!(retry(policy, || failing(&mut count, EREMOTEIO)).unwrap(), 3);
+        // ENODEV isn't transient
+        let mut count = 0;
+        assert!(retry(policy, || failing(&mut count, 19)).is_err());
+        assert_eq!(count, 1);
+        let mut count = 0;
+        assert!(retry(RetryPolicy::none(), || failing(&mut count, EIO)).is_err());
+        assert_eq!(count, 1);
+    }
+}
+
+// End of file
@@ -1,9 +1,15 @@
 //! # Defines a unit for Relative Humidity (which isn't in the measurements crate)
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
 /// Represents a relative humidity reading from the humidity sensor
+///
+/// With the `serde` feature, this is serialized as a plain percentage.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct RelativeHumidity {
     value: f64,
 }
@@ -16,6 +22,18 @@ impl RelativeHumidity {
     pub fn as_percent(&self) -> f64 {
         self.value
     }
+
+    /// Is the value a possible relative humidity, between 0 and 100%?
+    pub fn is_in_range(&self) -> bool {
+        self.value >= 0.0 && self.value <= 100.0
+    }
+
+    /// Returns the value clamped to between 0 and 100%.
+    pub fn clamped(&self) -> RelativeHumidity {
+        RelativeHumidity {
+            value: self.value.clamp(0.0, 100.0),
+        }
+    }
 }
 
 impl fmt::Display for RelativeHumidity {
@@ -24,4 +42,22 @@ impl fmt::Display for RelativeHumidity {
     }
 }
 
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn check_clamped() {
+        let rh = RelativeHumidity::from_percent(103.2);
+        assert!(!rh.is_in_range());
+        assert_eq!(rh.clamped().as_percent(), 100.0);
+        let rh = RelativeHumidity::from_percent(-0.5);
+        assert!(!rh.is_in_range());
+        assert_eq!(rh.clamped().as_percent(), 0.0);
+        let rh = RelativeHumidity::from_percent(45.0);
+        assert!(rh.is_in_range());
+        assert_eq!(rh.clamped().as_percent(), 45.0);
+    }
+}
+
 // End of file
@@ -31,16 +31,17 @@ struct AllData {
 
 extern "C" {
     WrapperContext* rtimulib_wrapper_create(void);
+    WrapperContext* rtimulib_wrapper_create_with_settings(const char* settings_directory, const char* product_type);
     void rtimulib_wrapper_destroy(WrapperContext* p_context);
     void rtimulib_set_sensors(WrapperContext* p_context, int gyro, int accel, int compass);
     int rtimulib_wrapper_imu_read(WrapperContext* p_context);
+    int rtimulib_wrapper_get_poll_interval(WrapperContext* p_context);
     int rtimulib_wrapper_get_imu_data(WrapperContext* p_context, AllData* p_output);
 }
 
-WrapperContext* rtimulib_wrapper_create(void) {
+static WrapperContext* rtimulib_wrapper_init(RTIMUSettings* p_settings) {
     WrapperContext* p_context = new WrapperContext;
-    // TODO: Should be ~/.config/sense_hat/RTIMULib
-    p_context->p_settings = new RTIMUSettings("RTIMULib");
+    p_context->p_settings = p_settings;
     p_context->p_imu = RTIMU::createIMU(p_context->p_settings);
     p_context->p_imu->IMUInit();
     p_context->p_imu->setSlerpPower(0.02);
@@ -48,6 +49,16 @@ WrapperContext* rtimulib_wrapper_create(void) {
     return p_context;
 }
 
+WrapperContext* rtimulib_wrapper_create(void) {
+    // TODO: Should be ~/.config/sense_hat/RTIMULib
+    return rtimulib_wrapper_init(new RTIMUSettings("RTIMULib"));
+}
+
+WrapperContext* rtimulib_wrapper_create_with_settings(const char* settings_directory, const char* product_type) {
+    // Loads <settings_directory>/<product_type>.ini
+    return rtimulib_wrapper_init(new RTIMUSettings(settings_directory, product_type));
+}
+
 void rtimulib_wrapper_destroy(WrapperContext* p_context) {
     // The settings object must outlive the IMU object
     delete p_context->p_imu;
@@ -65,6 +76,11 @@ int rtimulib_wrapper_imu_read(WrapperContext* p_context) {
     return p_context->p_imu->IMURead();
 }
 
+int rtimulib_wrapper_get_poll_interval(WrapperContext* p_context) {
+    // In milliseconds
+    return p_context->p_imu->IMUGetPollInterval();
+}
+
 int rtimulib_wrapper_get_imu_data(WrapperContext* p_context, AllData* p_output) {
     RTIMU_DATA imuData = p_context->p_imu->getIMUData();
     p_output->timestamp = imuData.timestamp;
@@ -0,0 +1,66 @@
+//! * Asking a framebuffer device for its format
+//!
+//! The `FBIOGET_VSCREENINFO` ioctl fills in a `struct fb_var_screeninfo`,
+//! whose first fields are the visible width and height and the pixel depth.
+//! It is asked of the device itself, so it works wherever the device is.
+
+use super::FramebufferFormat;
+use std::fs::File;
+use std::io;
+use std::os::raw::{c_int, c_ulong};
+use std::os::unix::io::AsRawFd;
+
+/// From `linux/fb.h`.
+const FBIOGET_VSCREENINFO: c_ulong = 0x4600;
+
+/// `struct fb_var_screeninfo` is 40 `__u32`s.
+const VAR_SCREENINFO_WORDS: usize = 40;
+const XRES: usize = 0;
+const YRES: usize = 1;
+const BITS_PER_PIXEL: usize = 6;
+
+extern "C" {
+    fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+}
+
+/// Reads the format of the framebuffer device open as `file`. Fails if it
+/// isn't a framebuffer device.
+pub(crate) fn read_format(file: &File) -> io::Result<FramebufferFormat> {
+    let mut info = [0u32; VAR_SCREENINFO_WORDS];
+    if unsafe { ioctl(file.as_raw_fd(), FBIOGET_VSCREENINFO, info.as_mut_ptr()) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(format_from_screeninfo(&info))
+}
+
+fn format_from_screeninfo(info: &[u32; VAR_SCREENINFO_WORDS]) -> FramebufferFormat {
+    FramebufferFormat {
+        width: info[XRES],
+        height: info[YRES],
+        bits_per_pixel: info[BITS_PER_PIXEL],
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn check_format_from_screeninfo() {
+        let mut info = [0u32; VAR_SCREENINFO_WORDS];
+        // xres, yres, xres_virtual, yres_virtual, xoffset, yoffset, bpp
+        info[..7].copy_from_slice(&[1920, 1080, 1920, 2160, 0, 0, 32]);
+        let format = format_from_screeninfo(&info);
+        assert_eq!(format.width, 1920);
+        assert_eq!(format.height, 1080);
+        assert_eq!(format.bits_per_pixel, 32);
+    }
+
+    #[test]
+    fn check_not_a_framebuffer() {
+        let file = File::open("/dev/null").unwrap();
+        assert!(read_format(&file).is_err());
+    }
+}
+
+// End of file
@@ -0,0 +1,79 @@
+//! * A quick check that the environmental sensors are working
+//!
+//! See `SenseHat::self_test`.
+
+/// The widest range of temperatures, in degrees C, either sensor should
+/// report.
+pub(crate) const TEMPERATURE_RANGE: (f64, f64) = (-40.0, 85.0);
+/// The LPS25H's measurement range, in hPa.
+pub(crate) const PRESSURE_RANGE: (f64, f64) = (260.0, 1260.0);
+/// Relative humidity, in percent, before clamping.
+pub(crate) const HUMIDITY_RANGE: (f64, f64) = (0.0, 100.0);
+
+/// The outcome of testing one sensor.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SelfTestResult {
+    Passed,
+    /// The WHO_AM_I register had the given value, so this isn't the
+    /// expected chip.
+    WrongChip(u8),
+    /// The sensor didn't produce a reading in time, e.g. because it is
+    /// powered down.
+    NoReading,
+    /// The named reading had the given value, which is outside the range
+    /// the sensor can measure.
+    OutOfRange(&'static str, f64),
+}
+
+/// The results of `SenseHat::self_test`, for each environmental sensor.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SelfTestReport {
+    /// The HTS221 humidity and temperature sensor.
+    pub humidity: SelfTestResult,
+    /// The LPS25H pressure and temperature sensor.
+    pub pressure: SelfTestResult,
+}
+
+impl SelfTestReport {
+    /// Did both sensors pass?
+    pub fn passed(&self) -> bool {
+        self.humidity == SelfTestResult::Passed && self.pressure == SelfTestResult::Passed
+    }
+}
+
+/// Checks that each `(name, value, range)` reading is within its range.
+pub(crate) fn check_ranges(readings: &[(&'static str, f64, (f64, f64))]) -> SelfTestResult {
+    for &(name, value, (min, max)) in readings {
+        // NaN isn't in any range, so fails too
+        if !(min..=max).contains(&value) {
+            return SelfTestResult::OutOfRange(name, value);
+        }
+    }
+    SelfTestResult::Passed
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn check_check_ranges() {
+        assert_eq!(
+            check_ranges(&[("temperature", 25.0, TEMPERATURE_RANGE)]),
+            SelfTestResult::Passed
+        );
+        assert_eq!(
+            check_ranges(&[
+                ("temperature", 25.0, TEMPERATURE_RANGE),
+                ("pressure", 100.0, PRESSURE_RANGE)
+            ]),
+            SelfTestResult::OutOfRange("pressure", 100.0)
+        );
+        match check_ranges(&[("humidity", f64::NAN, HUMIDITY_RANGE)]) {
+            SelfTestResult::OutOfRange("humidity", _) => {}
+            other => panic!("expected OutOfRange, got {:?}", other),
+        }
+    }
+}
+
+// End of file
@@ -0,0 +1,77 @@
+//! * Serialization of `measurements` types as plain numbers
+//!
+//! Angles are written in degrees, temperatures in degrees Celsius and
+//! pressures in hectopascals. Use with `#[serde(with = "serde_units")]`, or
+//! `serde_units::option` for `Option` fields.
+
+use measurements::{Angle, Pressure, Temperature};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// A measurement that is serialized as an `f64` in a fixed unit.
+pub(crate) trait Unit: Sized {
+    fn to_unit(&self) -> f64;
+    fn from_unit(value: f64) -> Self;
+}
+
+impl Unit for Angle {
+    fn to_unit(&self) -> f64 {
+        self.as_degrees()
+    }
+
+    fn from_unit(value: f64) -> Angle {
+        Angle::from_degrees(value)
+    }
+}
+
+impl Unit for Temperature {
+    fn to_unit(&self) -> f64 {
+        self.as_celsius()
+    }
+
+    fn from_unit(value: f64) -> Temperature {
+        Temperature::from_celsius(value)
+    }
+}
+
+impl Unit for Pressure {
+    fn to_unit(&self) -> f64 {
+        self.as_hectopascals()
+    }
+
+    fn from_unit(value: f64) -> Pressure {
+        Pressure::from_hectopascals(value)
+    }
+}
+
+pub(crate) fn serialize<T: Unit, S: Serializer>(
+    value: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    value.to_unit().serialize(serializer)
+}
+
+pub(crate) fn deserialize<'de, T: Unit, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<T, D::Error> {
+    f64::deserialize(deserializer).map(T::from_unit)
+}
+
+pub(crate) mod option {
+    use super::Unit;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub(crate) fn serialize<T: Unit, S: Serializer>(
+        value: &Option<T>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        value.as_ref().map(Unit::to_unit).serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, T: Unit, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<T>, D::Error> {
+        Option::<f64>::deserialize(deserializer).map(|v| v.map(T::from_unit))
+    }
+}
+
+// End of file
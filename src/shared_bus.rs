@@ -0,0 +1,125 @@
+//! * Sharing one I2C bus file descriptor between several chips
+//!
+//! Normally each chip gets its own `LinuxI2CDevice`, which means a file
+//! descriptor each for the same bus. A `SharedI2CBus` opens the bus once, and
+//! hands out a `SharedI2CDevice` for each chip, which sets the slave address
+//! before each transaction (when it has changed).
+
+use i2cdev::core::I2CDevice;
+use i2cdev::linux::{LinuxI2CDevice, LinuxI2CError};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+struct Bus {
+    i2cdev: LinuxI2CDevice,
+    /// The slave address `i2cdev` is currently set to.
+    address: u16,
+}
+
+/// An I2C bus, opened once and shared between several chips. The handles it
+/// gives out can't be used from other threads.
+#[derive(Clone)]
+pub struct SharedI2CBus {
+    bus: Rc<RefCell<Bus>>,
+}
+
+impl SharedI2CBus {
+    /// Opens the given I2C bus, e.g. `/dev/i2c-1`, with a 1 second timeout
+    /// for transfers on it.
+    pub fn open(path: &str) -> Result<SharedI2CBus, LinuxI2CError> {
+        // Any address will do to open it; use the first chip's.
+        let address = ::hts221::I2C_ADDRESS;
+        let i2cdev = LinuxI2CDevice::new(path, address)?;
+        ::i2c_timeout::set_timeout(&i2cdev, ::i2c_timeout::DEFAULT_TIMEOUT)?;
+        Ok(SharedI2CBus {
+            bus: Rc::new(RefCell::new(Bus { i2cdev, address })),
+        })
+    }
+
+    /// Returns a handle for the chip at the given address on this bus.
+    pub fn device(&self, address: u16) -> SharedI2CDevice {
+        SharedI2CDevice {
+            bus: self.bus.clone(),
+            address,
+        }
+    }
+}
+
+/// One chip on a `SharedI2CBus`.
+pub struct SharedI2CDevice {
+    bus: Rc<RefCell<Bus>>,
+    address: u16,
+}
+
+impl SharedI2CDevice {
+    /// Runs `f` on the bus, having set it to this chip's address.
+    fn with_bus<T, F>(&mut self, f: F) -> Result<T, LinuxI2CError>
+    where
+        F: FnOnce(&mut LinuxI2CDevice) -> Result<T, LinuxI2CError>,
+    {
+        let mut bus = self.bus.borrow_mut();
+        if bus.address != self.address {
+            bus.i2cdev.set_slave_address(self.address)?;
+            bus.address = self.address;
+        }
+        f(&mut bus.i2cdev)
+    }
+}
+
+impl I2CDevice for SharedI2CDevice {
+    type Error = LinuxI2CError;
+
+    fn read(&mut self, data: &mut [u8]) -> Result<(), LinuxI2CError> {
+        self.with_bus(|dev| dev.read(data))
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), LinuxI2CError> {
+        self.with_bus(|dev| dev.write(data))
+    }
+
+    fn smbus_write_quick(&mut self, bit: bool) -> Result<(), LinuxI2CError> {
+        self.with_bus(|dev| dev.smbus_write_quick(bit))
+    }
+
+    fn smbus_read_byte_data(&mut self, register: u8) -> Result<u8, LinuxI2CError> {
+        self.with_bus(|dev| dev.smbus_read_byte_data(register))
+    }
+
+    fn smbus_write_byte_data(&mut self, register: u8, value: u8) -> Result<(), LinuxI2CError> {
+        self.with_bus(|dev| dev.smbus_write_byte_data(register, value))
+    }
+
+    fn smbus_read_block_data(&mut self, register: u8) -> Result<Vec<u8>, LinuxI2CError> {
+        self.with_bus(|dev| dev.smbus_read_block_data(register))
+    }
+
+    fn smbus_read_i2c_block_data(
+        &mut self,
+        register: u8,
+        len: u8,
+    ) -> Result<Vec<u8>, LinuxI2CError> {
+        self.with_bus(|dev| dev.smbus_read_i2c_block_data(register, len))
+    }
+
+    fn smbus_write_block_data(&mut self, register: u8, values: &[u8]) -> Result<(), LinuxI2CError> {
+        self.with_bus(|dev| dev.smbus_write_block_data(register, values))
+    }
+
+    fn smbus_write_i2c_block_data(
+        &mut self,
+        register: u8,
+        values: &[u8],
+    ) -> Result<(), LinuxI2CError> {
+        self.with_bus(|dev| dev.smbus_write_i2c_block_data(register, values))
+    }
+
+    fn smbus_process_block(
+        &mut self,
+        register: u8,
+        values: &[u8],
+    ) -> Result<Vec<u8>, LinuxI2CError> {
+        self.with_bus(|dev| dev.smbus_process_block(register, values))
+    }
+}
+
+// End of file
@@ -0,0 +1,117 @@
+//! * Derived weather values, calculated from the raw sensor readings
+
+/// The lowest relative humidity, in percent, `dew_point_celsius` works from.
+const MIN_DEW_POINT_RH: f64 = 1.0;
+
+/// Calculate the dew point in degrees C from a temperature in degrees C and a
+/// relative humidity in percent, using the Magnus-Tetens approximation with
+/// the Sonntag (1990) coefficients.
+///
+/// This is accurate to within about 0.35 degrees C for temperatures between
+/// -45 and 60 degrees C, and relative humidities between 1% and 100%. Drier
+/// readings are treated as 1%, as the dew point of completely dry air is
+/// infinitely low.
+pub(crate) fn dew_point_celsius(temp_c: f64, rh_percent: f64) -> f64 {
+    const A: f64 = 17.62;
+    const B: f64 = 243.12;
+    let rh = rh_percent.max(MIN_DEW_POINT_RH);
+    let gamma = (rh / 100.0).ln() + (A * temp_c) / (B + temp_c);
+    (B * gamma) / (A - gamma)
+}
+
+/// Calculate the heat index (apparent temperature) in degrees C from a
+/// temperature in degrees C and a relative humidity in percent, using the
+/// Rothfusz regression from the US National Weather Service.
+///
+/// The regression is only valid from about 27 degrees C (80 degrees F)
+/// upwards; below that, the dry-bulb temperature is returned unchanged.
+pub(crate) fn heat_index_celsius(temp_c: f64, rh_percent: f64) -> f64 {
+    let t = temp_c * 9.0 / 5.0 + 32.0;
+    if t < 80.0 {
+        return temp_c;
+    }
+    let rh = rh_percent;
+    let hi = -42.379 + 2.049_015_23 * t + 10.143_331_27 * rh
+        - 0.224_755_41 * t * rh
+        - 0.006_837_83 * t * t
+        - 0.054_817_17 * rh * rh
+        + 0.001_228_74 * t * t * rh
+        + 0.000_852_82 * t * rh * rh
+        - 0.000_001_99 * t * t * rh * rh;
+    (hi - 32.0) * 5.0 / 9.0
+}
+
+/// Calculate the absolute humidity in grams of water per cubic metre from a
+/// temperature in degrees C and a relative humidity in percent.
+///
+/// The saturation vapour pressure is found using the Magnus formula (Bolton,
+/// 1980), and the air is treated as an ideal gas. This is accurate to about
+/// 0.1% between -30 and 35 degrees C.
+pub(crate) fn absolute_humidity_g_m3(temp_c: f64, rh_percent: f64) -> f64 {
+    // Saturation vapour pressure, in hPa
+    let saturation = 6.112 * ((17.67 * temp_c) / (temp_c + 243.5)).exp();
+    // Actual vapour pressure, in hPa
+    let vapour = saturation * rh_percent / 100.0;
+    // Divide by the specific gas constant for water vapour (461.5 J/(kg K)),
+    // converting hPa to Pa and kg to g.
+    vapour * 100_000.0 / (461.5 * (temp_c + 273.15))
+}
+
+/// Standard sea-level pressure, in hPa.
+pub(crate) const STANDARD_SEA_LEVEL_HPA: f64 = 1013.25;
+
+/// Calculate the altitude in metres from a pressure and the pressure at sea
+/// level (both in hPa), using the international barometric formula.
+pub(crate) fn altitude_metres(pressure_hpa: f64, sea_level_hpa: f64) -> f64 {
+    44_330.0 * (1.0 - (pressure_hpa / sea_level_hpa).powf(1.0 / 5.255))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn check_dew_point() {
+        // At 100% RH, the dew point is the air temperature
+        assert!((dew_point_celsius(20.0, 100.0) - 20.0).abs() < 0.01);
+        // 25 degrees C at 50% RH has a dew point of about 13.9 degrees C
+        assert!((dew_point_celsius(25.0, 50.0) - 13.9).abs() < 0.1);
+        // Bone-dry (or slightly negative) readings are treated as 1% RH
+        let dry = dew_point_celsius(25.0, 0.0);
+        assert!(dry.is_finite());
+        assert_eq!(dry, dew_point_celsius(25.0, 1.0));
+        assert_eq!(dew_point_celsius(25.0, -0.5), dry);
+    }
+
+    #[test]
+    fn check_absolute_humidity() {
+        // Saturated air at 20 degrees C holds about 17.3 g/m3
+        assert!((absolute_humidity_g_m3(20.0, 100.0) - 17.3).abs() < 0.1);
+        assert_eq!(absolute_humidity_g_m3(20.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn check_altitude() {
+        assert_eq!(altitude_metres(1013.25, STANDARD_SEA_LEVEL_HPA), 0.0);
+        // 900 hPa is roughly 990m up
+        assert!((altitude_metres(900.0, STANDARD_SEA_LEVEL_HPA) - 988.5).abs() < 1.0);
+    }
+
+    fn fahrenheit_to_celsius(f: f64) -> f64 {
+        (f - 32.0) * 5.0 / 9.0
+    }
+
+    #[test]
+    fn check_heat_index() {
+        // Reference points from the NOAA heat index table
+        let hi = heat_index_celsius(fahrenheit_to_celsius(90.0), 60.0);
+        assert!((hi - fahrenheit_to_celsius(100.0)).abs() < 0.5);
+        let hi = heat_index_celsius(fahrenheit_to_celsius(100.0), 40.0);
+        assert!((hi - fahrenheit_to_celsius(109.0)).abs() < 0.5);
+    }
+
+    #[test]
+    fn check_heat_index_below_threshold() {
+        assert_eq!(heat_index_celsius(20.0, 80.0), 20.0);
+    }
+}